@@ -1,17 +1,595 @@
-use bevy::prelude::*;
+use std::sync::Arc;
 
-use crate::dialogue::{DialogueChoice, DialogueData, DialogueLine};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::{Rng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
 
-#[derive(Component)]
+use crate::PLAYER_NAME;
+use crate::dialogue::{
+    DialogueChoice, DialogueData, DialogueLine, DialogueScene, DialogueTrigger, VariantMode,
+    conditions::{self, ConditionContext},
+    events::DialoguePriority,
+    resources::{DialogueRng, ReadLines, StringTables},
+    voice::Waveform,
+};
+
+#[derive(Component, Clone)]
 pub struct DialogueManager {
     pub original_camera_transform: Option<Transform>,
+    /// priority the running dialogue was started with
+    pub priority: DialoguePriority,
     pub current_scene: String,
     pub current_line: usize,
-    pub dialogue_data: DialogueData,
+    /// shared so the manager can be copied to look ahead, see [`DialogueManager::peek_shown_line`]
+    pub dialogue_data: Arc<DialogueData>,
     pub auto_mode: bool,
+    pub skip_mode: bool,
+    /// set every frame while the hold-to-skip action is pressed
+    pub skip_held: bool,
     pub waiting_for_choice: bool,
     pub current_choices: Vec<DialogueChoice>,
+    /// index of each entry of `current_choices` in the line's full choice list
+    pub current_choice_indices: Vec<usize>,
+    pub taken_choices: HashSet<ChoiceKey>,
+    /// set by `set_variable` triggers
+    pub variables: HashMap<String, serde_json::Value>,
+    pub focused_choice: usize,
+    pub choice_timeout: Option<ChoiceTimeout>,
     pub ephemeral_line: Option<DialogueLine>,
+    /// what `DialogueState.current_text` was rendered from, kept to re-render it in another locale
+    pub shown_text: Option<ShownText>,
+    /// set by a line with `end`, the next advance closes the dialogue
+    pub ending: bool,
+    /// scenes to return to once a called scene runs out of lines
+    pub call_stack: Vec<CallFrame>,
+    /// arguments of the scene currently being called
+    pub locals: HashMap<String, serde_json::Value>,
+    pub variant_states: HashMap<LineKey, VariantState>,
+    pub scene_visits: HashMap<String, u32>,
+    pub line_visits: HashMap<LineKey, u32>,
+    /// scene changes the systems haven't announced yet
+    pub scene_transitions: Vec<SceneTransition>,
+    /// triggers the systems haven't sent out yet, `set_variable` ones are already applied
+    pub fired_triggers: Vec<DialogueTrigger>,
+}
+
+impl ConditionContext for DialogueManager {
+    fn current_scene(&self) -> &str {
+        &self.current_scene
+    }
+
+    fn visits(&self, scene: &str) -> u32 {
+        self.scene_visits.get(scene).copied().unwrap_or(0)
+    }
+
+    fn variable(&self, name: &str) -> Option<&serde_json::Value> {
+        self.locals.get(name).or_else(|| self.variables.get(name))
+    }
+}
+
+/// guards against lines without text jumping between each other forever
+const MAX_PASSED_LINES: usize = 1000;
+
+impl DialogueManager {
+    pub fn new(dialogue_data: DialogueData) -> Self {
+        Self {
+            original_camera_transform: None,
+            priority: DialoguePriority::default(),
+            current_scene: String::new(),
+            current_line: 0,
+            dialogue_data: Arc::new(dialogue_data),
+            auto_mode: false,
+            skip_mode: false,
+            skip_held: false,
+            waiting_for_choice: false,
+            current_choices: Vec::new(),
+            current_choice_indices: Vec::new(),
+            taken_choices: HashSet::new(),
+            variables: HashMap::new(),
+            focused_choice: 0,
+            choice_timeout: None,
+            ephemeral_line: None,
+            shown_text: None,
+            ending: false,
+            call_stack: Vec::new(),
+            locals: HashMap::new(),
+            variant_states: HashMap::new(),
+            scene_visits: HashMap::new(),
+            line_visits: HashMap::new(),
+            scene_transitions: Vec::new(),
+            fired_triggers: Vec::new(),
+        }
+    }
+
+    /// moves to another scene and counts it as a visit, returning from a call doesn't use this
+    pub fn enter_scene(&mut self, scene: String, line: usize) {
+        *self.scene_visits.entry(scene.clone()).or_default() += 1;
+        self.transition(SceneTransition::Entered(scene.clone()));
+        self.current_scene = scene;
+        self.current_line = line;
+    }
+
+    /// leaves the current scene for another one
+    pub fn change_scene(&mut self, scene: String, line: usize) {
+        self.leave_scene();
+        self.enter_scene(scene, line);
+    }
+
+    fn leave_scene(&mut self) {
+        self.transition(SceneTransition::Left(self.current_scene.clone()));
+    }
+
+    /// records a scene change for the systems to announce, firing the `on_enter` or
+    /// `on_exit` triggers of the scene
+    fn transition(&mut self, transition: SceneTransition) {
+        let scenes = &self.dialogue_data.scenes;
+        let triggers = match &transition {
+            SceneTransition::Entered(scene) => scenes.get(scene).and_then(|s| s.on_enter.clone()),
+            SceneTransition::Left(scene) => scenes.get(scene).and_then(|s| s.on_exit.clone()),
+            SceneTransition::Resumed(_) => None,
+        };
+        self.fire_triggers(&triggers);
+        self.scene_transitions.push(transition);
+    }
+
+    /// applies `set_variable` triggers right away, so conditions checked later in the same
+    /// advance already see them, and queues every trigger for the systems to send out
+    pub fn fire_triggers(&mut self, triggers: &Option<Vec<DialogueTrigger>>) {
+        for trigger in triggers.iter().flatten() {
+            if trigger.trigger_type == "set_variable"
+                && let Some(value) = trigger.params.get("value")
+            {
+                self.variables.insert(trigger.target.clone(), value.clone());
+            }
+            self.fired_triggers.push(trigger.clone());
+        }
+    }
+
+    pub fn visit_current_line(&mut self) {
+        let key = LineKey {
+            scene: self.current_scene.clone(),
+            line: self.current_line,
+        };
+        *self.line_visits.entry(key).or_default() += 1;
+    }
+
+    /// a missing condition always holds, one that fails to evaluate never does
+    pub fn condition_holds(&self, condition: &Option<String>) -> bool {
+        let Some(condition) = condition else {
+            return true;
+        };
+
+        conditions::evaluate(condition, self).unwrap_or_else(|err| {
+            warn!("invalid condition \"{condition}\": {err}");
+            false
+        })
+    }
+
+    /// leaves the running scene and every caller still waiting on the call stack
+    pub fn leave_dialogue(&mut self) {
+        self.ending = true;
+        self.leave_scene();
+        for frame in std::mem::take(&mut self.call_stack).into_iter().rev() {
+            self.transition(SceneTransition::Left(frame.scene));
+        }
+    }
+
+    /// clears everything that only lives as long as one conversation
+    pub fn reset_conversation(&mut self) {
+        self.skip_mode = false;
+        self.ending = false;
+        self.call_stack.clear();
+        self.locals.clear();
+        self.waiting_for_choice = false;
+        self.current_choices.clear();
+        self.current_choice_indices.clear();
+        self.focused_choice = 0;
+        self.choice_timeout = None;
+        self.ephemeral_line = None;
+        self.shown_text = None;
+    }
+
+    /// the line the next advance would show with the scene defaults filled in,
+    /// returning from finished calls first
+    pub fn current_dialogue_line(&mut self) -> Option<DialogueLine> {
+        if self.ending {
+            return None;
+        }
+
+        loop {
+            let scene = self.dialogue_data.scenes.get(&self.current_scene);
+            if let Some(scene) = scene
+                && let Some(line) = scene.lines.get(self.current_line)
+            {
+                let mut line = line.clone();
+                line.auto_time = line.auto_time.or(scene.auto_time);
+                line.camera_target = line.camera_target.or_else(|| scene.camera_target.clone());
+                return Some(line);
+            }
+
+            self.leave_scene();
+            let Some(frame) = self.call_stack.pop() else {
+                // the scenes have been left, so asking again must not leave them twice
+                self.ending = true;
+                return None;
+            };
+            self.transition(SceneTransition::Resumed(frame.scene.clone()));
+            self.current_scene = frame.scene;
+            self.current_line = frame.line;
+            self.locals = frame.locals;
+        }
+    }
+
+    /// the next line to show, passing over lines whose condition fails and running silent
+    /// lines, which only carry triggers and directives
+    pub fn next_shown_line(&mut self) -> Option<DialogueLine> {
+        let mut passed_lines = 0;
+        while let Some(line) = self.current_dialogue_line() {
+            let skipped = !self.condition_holds(&line.condition);
+            if !skipped && !line.is_silent() {
+                return Some(line);
+            }
+
+            passed_lines += 1;
+            if passed_lines > MAX_PASSED_LINES {
+                error!(
+                    "gave up after passing {MAX_PASSED_LINES} lines in scene \"{}\"",
+                    self.current_scene
+                );
+                self.ending = true;
+                return None;
+            }
+
+            if skipped {
+                self.current_line += 1;
+            } else {
+                self.fire_triggers(&line.triggers);
+                self.finish_line(&line);
+            }
+        }
+        None
+    }
+
+    /// moves past a line that is done, following its `end`, `call` and jump directives
+    pub fn finish_line(&mut self, line: &DialogueLine) {
+        if line.end {
+            self.leave_dialogue();
+            return;
+        }
+
+        let next_line = match &line.next_scene {
+            Some(_) => line.next_line.unwrap_or(0),
+            None => line.next_line.unwrap_or(self.current_line + 1),
+        };
+
+        if let Some(call) = &line.call {
+            self.call_stack.push(CallFrame {
+                scene: line
+                    .next_scene
+                    .clone()
+                    .unwrap_or_else(|| self.current_scene.clone()),
+                line: next_line,
+                locals: std::mem::take(&mut self.locals),
+            });
+            self.locals = call.args.clone();
+            self.enter_scene(call.scene.clone(), 0);
+        } else if let Some(next_scene) = &line.next_scene {
+            self.change_scene(next_scene.clone(), next_line);
+        } else {
+            self.current_line = next_line;
+        }
+    }
+
+    /// the untranslated text to show for the current line, picking one of its variants
+    /// if it has any
+    pub fn line_text(&mut self, line: &DialogueLine, rng: &mut DialogueRng) -> ShownText {
+        let line_id = line.text_id(&self.current_scene, self.current_line);
+        let Some(variants) = line
+            .variants
+            .as_ref()
+            .filter(|variants| !variants.is_empty())
+        else {
+            return ShownText {
+                id: line_id,
+                source: line.text.clone(),
+            };
+        };
+
+        let key = LineKey {
+            scene: self.current_scene.clone(),
+            line: self.current_line,
+        };
+        let state = self.variant_states.entry(key).or_default();
+        let count = variants.len();
+
+        let index = match line.variant_mode {
+            VariantMode::Random => rng.0.gen_range(0..count),
+            VariantMode::Sequence => state.shown.min(count - 1),
+            VariantMode::Cycle => state.shown % count,
+            VariantMode::Visits => {
+                let visits = self.scene_visits.get(&self.current_scene).copied();
+                (visits.unwrap_or(1).max(1) as usize - 1).min(count - 1)
+            }
+            VariantMode::Shuffle => {
+                if state.bag.is_empty() {
+                    state.bag = (0..count).collect();
+                    state.bag.shuffle(&mut rng.0);
+                    // don't repeat the last variant of the previous round straight away
+                    if count > 1 && state.bag.last() == state.last.as_ref() {
+                        state.bag.swap(0, count - 1);
+                    }
+                }
+                state.bag.pop().unwrap()
+            }
+        };
+
+        state.shown += 1;
+        state.last = Some(index);
+        ShownText {
+            id: format!("{line_id}.variant.{index}"),
+            source: variants[index].clone(),
+        }
+    }
+
+    /// the running scene's title, translated
+    pub fn scene_title(&self, tables: &StringTables, locale: &str) -> Option<String> {
+        let title = self
+            .dialogue_data
+            .scenes
+            .get(&self.current_scene)?
+            .title
+            .as_ref()?;
+        let id = DialogueScene::title_id(&self.current_scene);
+        Some(tables.translate(locale, &id, title).into_owned())
+    }
+
+    /// translates and interpolates text for display
+    pub fn render(&self, shown: &ShownText, tables: &StringTables, locale: &str) -> String {
+        self.interpolate(&tables.translate(locale, &shown.id, &shown.source))
+    }
+
+    fn source_line(&self) -> Option<&DialogueLine> {
+        self.dialogue_data
+            .scenes
+            .get(&self.current_scene)
+            .and_then(|scene| scene.lines.get(self.current_line))
+    }
+
+    /// the presented choices with their text translated
+    pub fn localized_choices(&self, tables: &StringTables, locale: &str) -> Vec<DialogueChoice> {
+        let Some(line) = self.source_line() else {
+            return Vec::new();
+        };
+        let line_id = line.text_id(&self.current_scene, self.current_line);
+        let choices = line.choices.as_deref().unwrap_or_default();
+
+        self.current_choice_indices
+            .iter()
+            .filter_map(|&index| {
+                let mut choice = choices.get(index)?.clone();
+                let choice_id = choice.text_id(&line_id, index);
+                choice.text = tables
+                    .translate(locale, &choice_id, &choice.text)
+                    .to_string();
+                choice.player_text = choice.player_text.map(|text| {
+                    let id = format!("{choice_id}.player_text");
+                    tables.translate(locale, &id, &text).to_string()
+                });
+                Some(choice)
+            })
+            .collect()
+    }
+
+    /// the untranslated text echoed for the player after picking a choice of the current
+    /// line, or after letting it time out when `choice_index` is `None`
+    pub fn player_line_text(&self, choice_index: Option<usize>) -> Option<ShownText> {
+        let line = self.source_line()?;
+        let line_id = line.text_id(&self.current_scene, self.current_line);
+
+        let Some(choice_index) = choice_index else {
+            let source = line.silence.as_ref()?.player_text.clone()?;
+            return Some(ShownText {
+                id: format!("{line_id}.silence"),
+                source,
+            });
+        };
+
+        let choice = line.choices.as_ref()?.get(choice_index)?;
+        let choice_id = choice.text_id(&line_id, choice_index);
+        Some(match &choice.player_text {
+            Some(player_text) => ShownText {
+                id: format!("{choice_id}.player_text"),
+                source: player_text.clone(),
+            },
+            None => ShownText {
+                id: choice_id,
+                source: choice.text.clone(),
+            },
+        })
+    }
+
+    /// follows a choice of the current line, or its silence outcome when `choice_index` is
+    /// `None`, firing its triggers and queueing the player's echoed line
+    pub fn choose(&mut self, choice: &DialogueChoice, choice_index: Option<usize>) {
+        self.fire_triggers(&choice.triggers);
+
+        // the echoed line keeps its string table id so it's translated when shown
+        let player_text = self.player_line_text(choice_index);
+        self.ephemeral_line = player_text.map(|shown| DialogueLine {
+            id: Some(shown.id),
+            speaker: PLAYER_NAME.to_string(),
+            text: shown.source,
+            auto_time: choice.player_text_auto_time,
+            camera_target: Some(PLAYER_NAME.to_string()),
+            ..default()
+        });
+
+        if let Some(next_scene) = &choice.next_scene {
+            self.change_scene(next_scene.clone(), choice.next_line.unwrap_or(0));
+        } else if let Some(next_line) = choice.next_line {
+            self.current_line = next_line;
+        } else {
+            self.current_line += 1;
+        }
+
+        self.waiting_for_choice = false;
+        self.current_choices.clear();
+        self.current_choice_indices.clear();
+        self.focused_choice = 0;
+        self.choice_timeout = None;
+    }
+
+    /// replaces `{name}` with the scene argument or dialogue variable of that name
+    pub fn interpolate(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+
+            let value = after.find('}').and_then(|end| {
+                let name = &after[..end];
+                self.locals
+                    .get(name)
+                    .or_else(|| self.variables.get(name))
+                    .map(|value| (end, value))
+            });
+
+            match value {
+                Some((end, value)) => {
+                    match value {
+                        serde_json::Value::String(value) => result.push_str(value),
+                        value => result.push_str(&value.to_string()),
+                    }
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    result.push('{');
+                    rest = after;
+                }
+            }
+        }
+
+        result.push_str(rest);
+        result
+    }
+
+    pub fn choice_key(&self, choice_index: usize) -> ChoiceKey {
+        ChoiceKey {
+            scene: self.current_scene.clone(),
+            line: self.current_line,
+            choice: choice_index,
+        }
+    }
+
+    /// indices of the choices to show, hiding used up `once` choices and only
+    /// offering `fallback` choices when nothing else is left
+    pub fn presentable_choices(&self, choices: &[DialogueChoice]) -> Vec<usize> {
+        let available = |fallback: bool| -> Vec<usize> {
+            choices
+                .iter()
+                .enumerate()
+                .filter(|(i, choice)| {
+                    choice.fallback == fallback
+                        && !(choice.once && self.taken_choices.contains(&self.choice_key(*i)))
+                        && self.condition_holds(&choice.condition)
+                })
+                .map(|(i, _)| i)
+                .collect()
+        };
+
+        let regular = available(false);
+        if regular.is_empty() {
+            available(true)
+        } else {
+            regular
+        }
+    }
+
+    /// whether the next advance would show a line the player hasn't read yet,
+    /// echoed player lines and the end of the dialogue never count as unread
+    pub fn next_line_unread(&self, read_lines: &ReadLines) -> bool {
+        if self.ephemeral_line.is_some() {
+            return false;
+        }
+
+        self.peek_shown_line()
+            .is_some_and(|(key, _)| !read_lines.is_read(&key.scene, key.line))
+    }
+
+    /// the line the next advance will show and where it is, looking past the silent and
+    /// skipped lines in front of it on a copy, so their triggers only touch the copy
+    pub fn peek_shown_line(&self) -> Option<(LineKey, DialogueLine)> {
+        let mut peek = self.clone();
+        let line = peek.next_shown_line()?;
+        let key = LineKey {
+            scene: peek.current_scene,
+            line: peek.current_line,
+        };
+        Some((key, line))
+    }
+
+    /// whether a presented choice was already picked on an earlier visit to this line
+    pub fn is_choice_taken(&self, presented_index: usize) -> bool {
+        self.current_choice_indices
+            .get(presented_index)
+            .is_some_and(|&index| self.taken_choices.contains(&self.choice_key(index)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChoiceKey {
+    pub scene: String,
+    pub line: usize,
+    pub choice: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VariantState {
+    pub shown: usize,
+    pub last: Option<usize>,
+    /// variants left to show before a shuffled line reshuffles
+    pub bag: Vec<usize>,
+}
+
+/// a string table id and the source text used when the locale has no translation for it
+#[derive(Debug, Clone)]
+pub struct ShownText {
+    pub id: String,
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum SceneTransition {
+    Entered(String),
+    Left(String),
+    /// back in a caller once the scene it called ran out of lines
+    Resumed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub scene: String,
+    pub line: usize,
+    pub locals: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LineKey {
+    pub scene: String,
+    pub line: usize,
+}
+
+#[derive(Clone)]
+pub struct ChoiceTimeout {
+    pub timer: Timer,
+    /// index into the presented choices, not the line's full list
+    pub default_choice: Option<usize>,
+    pub silence: Option<DialogueChoice>,
 }
 
 #[derive(Component)]
@@ -20,11 +598,25 @@ pub struct DialogueTimer(pub Timer);
 #[derive(Component)]
 pub struct DialogueCamera;
 
+/// the voice-over of the line on screen, despawned once it finishes playing
+#[derive(Component)]
+pub struct VoiceClip;
+
 #[derive(Component)]
 pub struct Speaker {
     pub name: String,
 }
 
+/// how a speaker sounds when their lines are blipped out instead of voiced
+#[derive(Component, Debug, Clone, Copy)]
+pub struct BlipProfile {
+    /// base pitch in hz
+    pub pitch: f32,
+    pub waveform: Waveform,
+    /// how far each blip's pitch may stray from `pitch`, as a fraction of it
+    pub variance: f32,
+}
+
 #[derive(Component)]
 pub struct DialogueTarget;
 
@@ -38,3 +630,203 @@ pub struct MovementGoal {
     pub target: Vec3,
     pub speed: f32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a manager at the start of the first of `scenes`, by name
+    fn manager(scenes: &str) -> DialogueManager {
+        let json = format!(r#"{{"scenes": {scenes}}}"#);
+        let mut manager = DialogueManager::new(DialogueData::from_json(&json).unwrap());
+        let mut scene_names: Vec<&String> = manager.dialogue_data.scenes.keys().collect();
+        scene_names.sort();
+        let first = scene_names[0].clone();
+        manager.enter_scene(first, 0);
+        manager
+    }
+
+    fn set_variable(name: &str, value: &str) -> String {
+        format!(r#"{{"type": "set_variable", "target": "{name}", "params": {{"value": {value}}}}}"#)
+    }
+
+    #[test]
+    fn silent_lines_set_variables_before_the_next_condition() {
+        let mut manager = manager(&format!(
+            r#"{{"a": [
+                {{"triggers": [{}]}},
+                {{"speaker": "Joe", "text": "shown", "condition": "x == 1"}}
+            ]}}"#,
+            set_variable("x", "1")
+        ));
+
+        let line = manager.next_shown_line().unwrap();
+        assert_eq!(line.text, "shown");
+        assert_eq!(manager.fired_triggers.len(), 1);
+    }
+
+    #[test]
+    fn line_triggers_apply_before_its_choice_conditions() {
+        let mut manager = manager(&format!(
+            r#"{{"a": [
+                {{"speaker": "Joe", "text": "well?", "triggers": [{}], "choices": [
+                    {{"text": "hidden", "condition": "!x"}},
+                    {{"text": "shown", "condition": "x"}}
+                ]}}
+            ]}}"#,
+            set_variable("x", "true")
+        ));
+
+        let line = manager.next_shown_line().unwrap();
+        manager.fire_triggers(&line.triggers);
+        let choices = line.choices.as_deref().unwrap();
+        assert_eq!(manager.presentable_choices(choices), vec![1]);
+    }
+
+    #[test]
+    fn choice_triggers_apply_before_the_next_condition() {
+        let mut manager = manager(&format!(
+            r#"{{"a": [
+                {{"speaker": "Joe", "text": "well?", "choices": [
+                    {{"text": "yes", "triggers": [{}]}}
+                ]}},
+                {{"speaker": "Joe", "text": "no", "condition": "!agreed"}},
+                {{"speaker": "Joe", "text": "yes"}}
+            ]}}"#,
+            set_variable("agreed", "true")
+        ));
+
+        let line = manager.next_shown_line().unwrap();
+        let choice = line.choices.unwrap()[0].clone();
+        manager.choose(&choice, Some(0));
+        manager.ephemeral_line = None;
+        assert_eq!(manager.next_shown_line().unwrap().text, "yes");
+    }
+
+    #[test]
+    fn follows_labels_into_other_scenes() {
+        let mut manager = manager(
+            r#"{
+                "a": [{"speaker": "Joe", "text": "off we go", "next_scene": "b",
+                       "next_label": "outside"}],
+                "b": [
+                    {"speaker": "Joe", "text": "skipped"},
+                    {"label": "outside", "speaker": "Joe", "text": "here"}
+                ]
+            }"#,
+        );
+
+        let line = manager.next_shown_line().unwrap();
+        manager.finish_line(&line);
+        assert_eq!(manager.next_shown_line().unwrap().text, "here");
+        assert_eq!(
+            (manager.current_scene.as_str(), manager.current_line),
+            ("b", 1)
+        );
+        assert_eq!(manager.scene_visits["b"], 1);
+    }
+
+    #[test]
+    fn end_stops_in_the_middle_of_a_scene() {
+        let mut manager = manager(
+            r#"{"a": [
+                {"speaker": "Joe", "text": "bye", "end": true},
+                {"speaker": "Joe", "text": "never said"}
+            ]}"#,
+        );
+
+        let line = manager.next_shown_line().unwrap();
+        manager.finish_line(&line);
+        assert!(manager.next_shown_line().is_none());
+        assert!(manager.ending);
+        assert!(matches!(
+            manager.scene_transitions.last(),
+            Some(SceneTransition::Left(scene)) if scene == "a"
+        ));
+        // asking again doesn't leave the scene a second time
+        let transitions = manager.scene_transitions.len();
+        assert!(manager.current_dialogue_line().is_none());
+        assert_eq!(manager.scene_transitions.len(), transitions);
+    }
+
+    #[test]
+    fn next_line_jumps_within_the_scene() {
+        let mut manager = manager(
+            r#"{"a": [
+                {"speaker": "Joe", "text": "one", "next_line": 2},
+                {"speaker": "Joe", "text": "skipped"},
+                {"speaker": "Joe", "text": "three"}
+            ]}"#,
+        );
+
+        let line = manager.next_shown_line().unwrap();
+        manager.finish_line(&line);
+        assert_eq!(manager.next_shown_line().unwrap().text, "three");
+    }
+
+    #[test]
+    fn calls_return_to_the_caller_with_its_locals() {
+        let mut manager = manager(
+            r#"{
+                "a": [
+                    {"speaker": "Joe", "text": "calling", "call": {"scene": "b", "args": {"who": "Joe"}}},
+                    {"speaker": "Joe", "text": "back in a"}
+                ],
+                "b": [
+                    {"speaker": "{who}", "text": "in b", "call": {"scene": "c", "args": {"who": "Peter"}}},
+                    {"speaker": "{who}", "text": "back in b"}
+                ],
+                "c": [{"speaker": "{who}", "text": "in c"}]
+            }"#,
+        );
+
+        let mut said = Vec::new();
+        while let Some(line) = manager.next_shown_line() {
+            said.push(format!(
+                "{}: {}",
+                manager.interpolate(&line.speaker),
+                line.text
+            ));
+            manager.finish_line(&line);
+        }
+
+        assert_eq!(
+            said,
+            [
+                "Joe: calling",
+                "Joe: in b",
+                "Peter: in c",
+                "Joe: back in b",
+                "Joe: back in a",
+            ]
+        );
+        assert!(manager.call_stack.is_empty());
+        assert!(manager.locals.is_empty());
+    }
+
+    #[test]
+    fn shuffled_variants_show_each_once_per_round_without_repeats() {
+        let mut manager = manager(
+            r#"{"a": [{"speaker": "Joe", "variants": ["one", "two", "three"],
+                       "variant_mode": "shuffle"}]}"#,
+        );
+        let line = manager.next_shown_line().unwrap();
+
+        for seed in 0..20 {
+            let mut rng = DialogueRng::seeded(seed);
+            manager.variant_states.clear();
+            let shown: Vec<String> = (0..30)
+                .map(|_| manager.line_text(&line, &mut rng).source)
+                .collect();
+
+            for round in shown.chunks(3) {
+                let mut round = round.to_vec();
+                round.sort();
+                assert_eq!(round, ["one", "three", "two"], "seed {seed}");
+            }
+            for pair in shown.windows(2) {
+                assert_ne!(pair[0], pair[1], "seed {seed}");
+            }
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! small expression language used by the `condition` field of lines and choices, e.g.
+//! `visits("intro") > 1 && !met_peter`
+
+use std::cmp::Ordering;
+
+/// what a condition can look at while it's evaluated
+pub trait ConditionContext {
+    fn current_scene(&self) -> &str;
+    fn visits(&self, scene: &str) -> u32;
+    fn variable(&self, name: &str) -> Option<&serde_json::Value>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Variable(String),
+    Call(String, Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Number(value) => *value != 0.0,
+            Value::String(value) => !value.is_empty(),
+        }
+    }
+
+    fn from_json(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Bool(value) => Value::Bool(*value),
+            serde_json::Value::Number(value) => Value::Number(value.as_f64().unwrap_or_default()),
+            serde_json::Value::String(value) => Value::String(value.clone()),
+            _ => Value::Null,
+        }
+    }
+}
+
+pub fn evaluate(condition: &str, context: &impl ConditionContext) -> Result<bool, String> {
+    Ok(parse(condition)?.eval(context))
+}
+
+pub fn parse(condition: &str) -> Result<Expr, String> {
+    let tokens = tokenize(condition)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.or()?;
+
+    match parser.tokens.get(parser.pos) {
+        Some(token) => Err(format!("unexpected {token:?}")),
+        None => Ok(expr),
+    }
+}
+
+impl Expr {
+    pub fn eval(&self, context: &impl ConditionContext) -> bool {
+        self.value(context).truthy()
+    }
+
+    fn value(&self, context: &impl ConditionContext) -> Value {
+        match self {
+            Expr::Bool(value) => Value::Bool(*value),
+            Expr::Number(value) => Value::Number(*value),
+            Expr::String(value) => Value::String(value.clone()),
+            Expr::Variable(name) => context
+                .variable(name)
+                .map(Value::from_json)
+                .unwrap_or(Value::Null),
+            Expr::Call(name, args) => call(name, args, context),
+            Expr::Not(expr) => Value::Bool(!expr.eval(context)),
+            Expr::And(left, right) => Value::Bool(left.eval(context) && right.eval(context)),
+            Expr::Or(left, right) => Value::Bool(left.eval(context) || right.eval(context)),
+            Expr::Compare(left, op, right) => {
+                let ordering = compare(&left.value(context), &right.value(context));
+                Value::Bool(match op {
+                    CompareOp::Eq => ordering == Some(Ordering::Equal),
+                    CompareOp::Ne => ordering != Some(Ordering::Equal),
+                    CompareOp::Lt => ordering == Some(Ordering::Less),
+                    CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    CompareOp::Gt => ordering == Some(Ordering::Greater),
+                    CompareOp::Ge => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                })
+            }
+        }
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.partial_cmp(right),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        (Value::Bool(left), Value::Bool(right)) => Some(left.cmp(right)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+fn call(name: &str, args: &[Expr], context: &impl ConditionContext) -> Value {
+    match name {
+        // `visits()` on its own counts the current scene
+        "visits" => {
+            let scene = match args.first().map(|arg| arg.value(context)) {
+                Some(Value::String(scene)) => scene,
+                _ => context.current_scene().to_string(),
+            };
+            Value::Number(context.visits(&scene) as f64)
+        }
+        _ => Value::Null,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Ident(String),
+    LParen,
+    RParen,
+    Comma,
+    Not,
+    And,
+    Or,
+    Compare(CompareOp),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '&' if next == Some('&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '|' if next == Some('|') => {
+                tokens.push(Token::Or);
+                i += 2;
+            }
+            '=' if next == Some('=') => {
+                tokens.push(Token::Compare(CompareOp::Eq));
+                i += 2;
+            }
+            '!' if next == Some('=') => {
+                tokens.push(Token::Compare(CompareOp::Ne));
+                i += 2;
+            }
+            '!' => {
+                tokens.push(Token::Not);
+                i += 1;
+            }
+            '<' | '>' => {
+                let op = match (c, next == Some('=')) {
+                    ('<', false) => CompareOp::Lt,
+                    ('<', true) => CompareOp::Le,
+                    ('>', false) => CompareOp::Gt,
+                    _ => CompareOp::Ge,
+                };
+                tokens.push(Token::Compare(op));
+                i += if next == Some('=') { 2 } else { 1 };
+            }
+            '"' | '\'' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&d| d == c)
+                    .ok_or("unterminated string")?;
+                tokens.push(Token::String(chars[i + 1..i + 1 + end].iter().collect()));
+                i += end + 2;
+            }
+            c if c.is_ascii_digit() => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|d| d.is_ascii_digit() || **d == '.')
+                    .count();
+                let number: String = chars[i..i + len].iter().collect();
+                let number = number
+                    .parse()
+                    .map_err(|_| format!("invalid number {number}"))?;
+                tokens.push(Token::Number(number));
+                i += len;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|d| d.is_alphanumeric() || **d == '_' || **d == '.')
+                    .count();
+                tokens.push(Token::Ident(chars[i..i + len].iter().collect()));
+                i += len;
+            }
+            c => return Err(format!("unexpected character '{c}'")),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {expected:?}, found {token:?}")),
+            None => Err(format!("expected {expected:?}")),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let left = self.primary()?;
+        if let Some(&Token::Compare(op)) = self.peek() {
+            self.pos += 1;
+            let right = self.primary()?;
+            return Ok(Expr::Compare(Box::new(left), op, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::String(value)) => Ok(Expr::String(value)),
+            Some(Token::Ident(name)) if name == "true" => Ok(Expr::Bool(true)),
+            Some(Token::Ident(name)) if name == "false" => Ok(Expr::Bool(false)),
+            Some(Token::Ident(name)) => {
+                if self.peek() != Some(&Token::LParen) {
+                    return Ok(Expr::Variable(name));
+                }
+                if name != "visits" {
+                    return Err(format!("unknown function {name}"));
+                }
+
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    args.push(self.or()?);
+                    while self.peek() == Some(&Token::Comma) {
+                        self.pos += 1;
+                        args.push(self.or()?);
+                    }
+                }
+                self.expect(Token::RParen)?;
+                Ok(Expr::Call(name, args))
+            }
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of condition".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    struct MockContext {
+        scene: String,
+        visits: HashMap<String, u32>,
+        variables: HashMap<String, serde_json::Value>,
+    }
+
+    impl MockContext {
+        fn new() -> Self {
+            Self {
+                scene: "bar".to_string(),
+                visits: HashMap::from([("bar".to_string(), 2), ("intro".to_string(), 1)]),
+                variables: HashMap::from([
+                    ("met_peter".to_string(), json!(true)),
+                    ("beers".to_string(), json!(3)),
+                    ("name".to_string(), json!("Joe")),
+                    ("empty".to_string(), json!("")),
+                ]),
+            }
+        }
+
+        fn with(mut self, name: &str, value: serde_json::Value) -> Self {
+            self.variables.insert(name.to_string(), value);
+            self
+        }
+    }
+
+    impl ConditionContext for MockContext {
+        fn current_scene(&self) -> &str {
+            &self.scene
+        }
+
+        fn visits(&self, scene: &str) -> u32 {
+            self.visits.get(scene).copied().unwrap_or_default()
+        }
+
+        fn variable(&self, name: &str) -> Option<&serde_json::Value> {
+            self.variables.get(name)
+        }
+    }
+
+    fn eval(condition: &str, context: &MockContext) -> bool {
+        evaluate(condition, context).unwrap()
+    }
+
+    fn var(name: &str) -> Box<Expr> {
+        Box::new(Expr::Variable(name.to_string()))
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_and_and_tighter_than_or() {
+        assert_eq!(
+            parse("!a && b || c").unwrap(),
+            Expr::Or(
+                Box::new(Expr::And(Box::new(Expr::Not(var("a"))), var("b"))),
+                var("c")
+            )
+        );
+        assert_eq!(
+            parse("a || b && c").unwrap(),
+            Expr::Or(var("a"), Box::new(Expr::And(var("b"), var("c"))))
+        );
+    }
+
+    #[test]
+    fn evaluates_with_precedence() {
+        let context = MockContext::new()
+            .with("a", json!(true))
+            .with("b", json!(false))
+            .with("c", json!(true));
+        assert!(eval("!a && b || c", &context));
+        assert!(!eval("!a && (b || c)", &context));
+        assert!(eval("a || b && false", &context));
+        assert!(!eval("!(a || b)", &context));
+    }
+
+    #[test]
+    fn compares_numbers_strings_and_bools() {
+        let context = MockContext::new();
+        assert!(eval("beers == 3", &context));
+        assert!(eval("beers >= 3 && beers < 3.5", &context));
+        assert!(eval("name == 'Joe'", &context));
+        assert!(eval("name < \"Peter\"", &context));
+        assert!(eval("met_peter == true", &context));
+        assert!(eval("true > false", &context));
+    }
+
+    #[test]
+    fn mixed_types_are_never_equal_or_ordered() {
+        let context = MockContext::new();
+        assert!(!eval("beers == '3'", &context));
+        assert!(eval("beers != '3'", &context));
+        assert!(!eval("beers < 'a'", &context));
+        assert!(!eval("beers >= 'a'", &context));
+        assert!(!eval("met_peter == 1", &context));
+    }
+
+    #[test]
+    fn missing_variables_are_null() {
+        let context = MockContext::new();
+        assert!(!eval("unknown", &context));
+        assert!(eval("!unknown", &context));
+        assert!(eval("unknown == also_unknown", &context));
+        assert!(!eval("unknown == 0", &context));
+    }
+
+    #[test]
+    fn truthiness() {
+        let context = MockContext::new().with("zero", json!(0));
+        assert!(eval("met_peter", &context));
+        assert!(eval("beers", &context));
+        assert!(!eval("zero", &context));
+        assert!(eval("name", &context));
+        assert!(!eval("empty", &context));
+        assert!(!eval("''", &context));
+    }
+
+    #[test]
+    fn visits_defaults_to_the_current_scene() {
+        let context = MockContext::new();
+        assert!(eval("visits() == 2", &context));
+        assert!(eval("visits('intro') == 1", &context));
+        assert!(eval("visits(\"never\") == 0", &context));
+        assert!(eval("visits(\"intro\") < visits()", &context));
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        for condition in [
+            "",
+            "name == 'Joe",
+            "beers # 3",
+            "drinks(3)",
+            "(met_peter",
+            "met_peter)",
+            "met_peter &&",
+            "visits('bar'",
+            "1.2.3 > 0",
+            "a b",
+        ] {
+            assert!(parse(condition).is_err(), "{condition:?} should not parse");
+            assert!(evaluate(condition, &MockContext::new()).is_err());
+        }
+    }
+}
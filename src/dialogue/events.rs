@@ -1,13 +1,42 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 
-use crate::dialogue::DialogueTrigger;
+use crate::dialogue::{
+    DialogueChoice, DialogueTrigger,
+    input::{DialogueAction, InputBinding},
+    resources::LogFormat,
+};
 
 #[derive(Event)]
 pub struct ResetSceneEvent;
 
+/// writes the dialogue log to a new file in `saves/transcripts`
 #[derive(Event)]
+pub struct ExportLogEvent(pub LogFormat);
+
+/// compared with the running dialogue's priority: higher interrupts it, equal waits in the
+/// queue until it's over and lower is dropped
+#[derive(Event, Clone)]
 pub struct StartDialogueEvent {
     pub scene_name: String,
+    pub priority: DialoguePriority,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DialoguePriority {
+    /// ambient chatter that shouldn't get in the way of anything
+    Low,
+    #[default]
+    Normal,
+    /// story beats that cut off whatever is being said
+    High,
+}
+
+/// ends the running dialogue early, then moves on to the next queued one
+#[derive(Event)]
+pub struct CancelDialogueEvent {
+    pub clear_queue: bool,
 }
 
 #[derive(Event)]
@@ -16,6 +45,9 @@ pub struct NextDialogueEvent;
 #[derive(Event)]
 pub struct ToggleAutoEvent;
 
+#[derive(Event)]
+pub struct ToggleSkipEvent;
+
 #[derive(Event)]
 pub struct MakeChoiceEvent {
     pub choice_index: usize,
@@ -23,3 +55,60 @@ pub struct MakeChoiceEvent {
 
 #[derive(Event, Debug)]
 pub struct ActionTriggerEvent(pub DialogueTrigger);
+
+#[derive(Event)]
+pub struct RebindActionEvent {
+    pub action: DialogueAction,
+    pub inputs: Vec<InputBinding>,
+}
+
+/// steps back to the previous line, or to the previous choice point with `to_choice`
+#[derive(Event)]
+pub struct RollbackEvent {
+    pub to_choice: bool,
+}
+
+// the events below are published by the dialogue for game code, both as regular events
+// and as observer triggers
+
+#[derive(Event, Debug, Clone)]
+pub struct DialogueStartedEvent {
+    pub scene: String,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct LineShownEvent {
+    pub scene: String,
+    /// `None` for the echoed player line of a choice
+    pub line: Option<usize>,
+    pub speaker: String,
+    pub text: String,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct ChoicesPresentedEvent {
+    pub scene: String,
+    pub line: usize,
+    pub choices: Vec<DialogueChoice>,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct ChoiceMadeEvent {
+    pub scene: String,
+    pub line: usize,
+    /// index into the line's full choice list, `None` when a timed choice ran out in silence
+    pub choice: Option<usize>,
+}
+
+/// sent when a scene is entered and when a called scene returns to its caller
+#[derive(Event, Debug, Clone)]
+pub struct SceneChangedEvent {
+    pub scene: String,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct DialogueEndedEvent {
+    pub scene: String,
+    /// the dialogue was cancelled or interrupted before reaching its end
+    pub cancelled: bool,
+}
@@ -0,0 +1,229 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::dialogue::resources::JsonFile;
+
+pub const INPUT_SETTINGS_PATH: &str = "settings/input.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DialogueAction {
+    Advance,
+    ToggleAuto,
+    ToggleLog,
+    Skip,
+    HoldSkip,
+    StartDialogue,
+    Cancel,
+    Rollback,
+    RollbackToChoice,
+    ChoiceUp,
+    ChoiceDown,
+    ChoiceConfirm,
+    /// zero based, so `SelectChoice(0)` picks the first choice on screen
+    SelectChoice(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionBindings {
+    pub action: DialogueAction,
+    pub inputs: Vec<InputBinding>,
+}
+
+// stored as a list instead of a map since serde_json only allows string keys,
+// and `SelectChoice` carries an index
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "SavedInputMap")]
+pub struct InputMap {
+    pub bindings: Vec<ActionBindings>,
+}
+
+/// the bindings as saved, which only override the defaults of the actions they list, so
+/// actions added after the file was written still get an input
+#[derive(Deserialize)]
+struct SavedInputMap {
+    bindings: Vec<ActionBindings>,
+}
+
+impl From<SavedInputMap> for InputMap {
+    fn from(saved: SavedInputMap) -> Self {
+        let mut input_map = InputMap::default();
+        for bindings in saved.bindings {
+            input_map.rebind(bindings.action, bindings.inputs);
+        }
+        input_map
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use DialogueAction::*;
+        use InputBinding::{Gamepad, Key};
+
+        let mut bindings = vec![
+            ActionBindings {
+                action: Advance,
+                inputs: vec![Key(KeyCode::Space), Gamepad(GamepadButton::South)],
+            },
+            ActionBindings {
+                action: ToggleAuto,
+                inputs: vec![Key(KeyCode::KeyA), Gamepad(GamepadButton::North)],
+            },
+            ActionBindings {
+                action: ToggleLog,
+                inputs: vec![Key(KeyCode::KeyL), Gamepad(GamepadButton::Select)],
+            },
+            ActionBindings {
+                action: Skip,
+                inputs: vec![Key(KeyCode::KeyS), Gamepad(GamepadButton::RightTrigger)],
+            },
+            ActionBindings {
+                action: HoldSkip,
+                inputs: vec![
+                    Key(KeyCode::ControlLeft),
+                    Gamepad(GamepadButton::RightTrigger2),
+                ],
+            },
+            ActionBindings {
+                action: StartDialogue,
+                inputs: vec![Key(KeyCode::KeyT), Gamepad(GamepadButton::Start)],
+            },
+            ActionBindings {
+                action: Cancel,
+                inputs: vec![Key(KeyCode::Escape), Gamepad(GamepadButton::East)],
+            },
+            ActionBindings {
+                action: Rollback,
+                inputs: vec![Key(KeyCode::Backspace), Gamepad(GamepadButton::LeftTrigger)],
+            },
+            ActionBindings {
+                action: RollbackToChoice,
+                inputs: vec![Key(KeyCode::PageUp), Gamepad(GamepadButton::LeftTrigger2)],
+            },
+            ActionBindings {
+                action: ChoiceUp,
+                inputs: vec![Key(KeyCode::ArrowUp), Gamepad(GamepadButton::DPadUp)],
+            },
+            ActionBindings {
+                action: ChoiceDown,
+                inputs: vec![Key(KeyCode::ArrowDown), Gamepad(GamepadButton::DPadDown)],
+            },
+            ActionBindings {
+                action: ChoiceConfirm,
+                inputs: vec![
+                    Key(KeyCode::Enter),
+                    Key(KeyCode::NumpadEnter),
+                    Gamepad(GamepadButton::South),
+                ],
+            },
+        ];
+
+        let digits = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        for (i, digit) in digits.into_iter().enumerate() {
+            bindings.push(ActionBindings {
+                action: SelectChoice(i),
+                inputs: vec![Key(digit)],
+            });
+        }
+
+        Self { bindings }
+    }
+}
+
+impl JsonFile for InputMap {
+    const DESCRIPTION: &str = "input settings";
+}
+
+impl InputMap {
+    pub fn inputs(&self, action: DialogueAction) -> &[InputBinding] {
+        self.bindings
+            .iter()
+            .find(|bindings| bindings.action == action)
+            .map(|bindings| bindings.inputs.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// replaces every input bound to `action`
+    pub fn rebind(&mut self, action: DialogueAction, inputs: Vec<InputBinding>) {
+        match self.bindings.iter_mut().find(|b| b.action == action) {
+            Some(bindings) => bindings.inputs = inputs,
+            None => self.bindings.push(ActionBindings { action, inputs }),
+        }
+    }
+}
+
+/// bundles every input source an [`InputBinding`] can refer to
+#[derive(SystemParam)]
+pub struct DialogueInput<'w, 's> {
+    pub input_map: Res<'w, InputMap>,
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    gamepads: Query<'w, 's, &'static Gamepad>,
+}
+
+impl DialogueInput<'_, '_> {
+    pub fn just_pressed(&self, action: DialogueAction) -> bool {
+        self.input_map
+            .inputs(action)
+            .iter()
+            .any(|input| match *input {
+                InputBinding::Key(key) => self.keys.just_pressed(key),
+                InputBinding::Mouse(button) => self.mouse.just_pressed(button),
+                InputBinding::Gamepad(button) => self
+                    .gamepads
+                    .iter()
+                    .any(|gamepad| gamepad.just_pressed(button)),
+            })
+    }
+
+    pub fn pressed(&self, action: DialogueAction) -> bool {
+        self.input_map
+            .inputs(action)
+            .iter()
+            .any(|input| match *input {
+                InputBinding::Key(key) => self.keys.pressed(key),
+                InputBinding::Mouse(button) => self.mouse.pressed(button),
+                InputBinding::Gamepad(button) => {
+                    self.gamepads.iter().any(|gamepad| gamepad.pressed(button))
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_bindings_only_replace_the_actions_they_list() {
+        let saved = r#"{"bindings": [{"action": "Advance", "inputs": [{"Key": "Enter"}]}]}"#;
+        let input_map: InputMap = serde_json::from_str(saved).unwrap();
+        let defaults = InputMap::default();
+
+        assert_eq!(
+            input_map.inputs(DialogueAction::Advance),
+            [InputBinding::Key(KeyCode::Enter)]
+        );
+        assert_eq!(
+            input_map.inputs(DialogueAction::Cancel),
+            defaults.inputs(DialogueAction::Cancel)
+        );
+        assert_eq!(input_map.bindings.len(), defaults.bindings.len());
+    }
+}
@@ -1,51 +1,606 @@
 pub mod components;
+pub mod conditions;
 pub mod events;
+pub mod input;
 pub mod resources;
+pub mod stage;
+pub mod states;
 pub mod systems;
+pub mod tools;
+pub mod voice;
+
+use std::{fmt, time::SystemTime};
 
 use events::*;
 use serde::{Deserialize, Serialize};
+use states::*;
 use systems::*;
 
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{audio::AddAudioSource, prelude::*, utils::HashMap};
 use bevy_egui::EguiPlugin;
 
+use crate::PLAYER_NAME;
+
+/// something said or picked in a dialogue, see [`resources::DialogueLog`]
+#[derive(Clone)]
+pub struct DialogueLogEntry {
+    pub time: SystemTime,
+    pub scene: String,
+    /// `None` for the player's echoed lines, which aren't lines of the scene
+    pub line: Option<usize>,
+    pub content: LogContent,
+}
+
+impl DialogueLogEntry {
+    /// `scene:line`, or just the scene for echoed player lines
+    pub fn location(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{line}", self.scene),
+            None => self.scene.clone(),
+        }
+    }
+
+    /// choices count as the player's
+    pub fn speaker(&self) -> &str {
+        match &self.content {
+            LogContent::Line { speaker, .. } => speaker,
+            LogContent::Choices { .. } => PLAYER_NAME,
+        }
+    }
+}
+
 #[derive(Clone)]
-pub enum DialogueLogEntry {
+pub enum LogContent {
     Line {
         speaker: String,
         text: String,
     },
     Choices {
         options: Vec<DialogueChoice>,
-        selected_index: usize,
+        /// `None` when the choice timed out into its silence outcome
+        selected_index: Option<usize>,
     },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DialogueData {
-    pub scenes: HashMap<String, Vec<DialogueLine>>,
+    pub scenes: HashMap<String, DialogueScene>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// a scene is either a bare array of lines or an object with `lines` and scene-wide settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "serde_json::Value", into = "SceneForm")]
+pub struct DialogueScene {
+    pub title: Option<String>,
+    /// used by lines that don't set their own `auto_time`
+    pub auto_time: Option<f32>,
+    /// used by lines that don't set their own `camera_target`
+    pub camera_target: Option<String>,
+    /// fired when the scene is entered by starting, jumping or calling into it
+    pub on_enter: Option<Vec<DialogueTrigger>>,
+    /// fired when the scene is left by jumping away, returning from it or ending the dialogue
+    pub on_exit: Option<Vec<DialogueTrigger>>,
+    pub tags: Vec<String>,
+    pub lines: Vec<DialogueLine>,
+}
+
+impl DialogueScene {
+    /// the string table id of the scene's title
+    pub fn title_id(scene_name: &str) -> String {
+        format!("{scene_name}.title")
+    }
+
+    fn has_metadata(&self) -> bool {
+        self.title.is_some()
+            || self.auto_time.is_some()
+            || self.camera_target.is_some()
+            || self.on_enter.is_some()
+            || self.on_exit.is_some()
+            || !self.tags.is_empty()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SceneForm {
+    Lines(Vec<DialogueLine>),
+    Scene(SceneObject),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SceneObject {
+    title: Option<String>,
+    auto_time: Option<f32>,
+    camera_target: Option<String>,
+    on_enter: Option<Vec<DialogueTrigger>>,
+    on_exit: Option<Vec<DialogueTrigger>>,
+    #[serde(default)]
+    tags: Vec<String>,
+    lines: Vec<DialogueLine>,
+}
+
+// picks the form up front instead of using an untagged enum, so mistakes inside a
+// scene keep serde's error message rather than "did not match any variant"
+impl TryFrom<serde_json::Value> for DialogueScene {
+    type Error = serde_json::Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        if value.is_array() {
+            return Ok(DialogueScene {
+                lines: serde_json::from_value(value)?,
+                ..default()
+            });
+        }
+
+        let scene: SceneObject = serde_json::from_value(value)?;
+        Ok(DialogueScene {
+            title: scene.title,
+            auto_time: scene.auto_time,
+            camera_target: scene.camera_target,
+            on_enter: scene.on_enter,
+            on_exit: scene.on_exit,
+            tags: scene.tags,
+            lines: scene.lines,
+        })
+    }
+}
+
+// scenes without any settings are written back in the short form
+impl From<DialogueScene> for SceneForm {
+    fn from(scene: DialogueScene) -> Self {
+        if !scene.has_metadata() {
+            return SceneForm::Lines(scene.lines);
+        }
+
+        SceneForm::Scene(SceneObject {
+            title: scene.title,
+            auto_time: scene.auto_time,
+            camera_target: scene.camera_target,
+            on_enter: scene.on_enter,
+            on_exit: scene.on_exit,
+            tags: scene.tags,
+            lines: scene.lines,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum DialogueDataError {
+    Parse(serde_json::Error),
+    DuplicateLabel {
+        scene: String,
+        label: String,
+    },
+    MissingLabel {
+        scene: String,
+        label: String,
+    },
+    MissingScene {
+        scene: String,
+    },
+    /// two strings resolve to the same string table id, explicitly or implicitly
+    DuplicateId {
+        id: String,
+        first: String,
+        second: String,
+    },
+    InvalidCondition {
+        scene: String,
+        condition: String,
+        message: String,
+    },
+    /// a line can't both pick a choice and stay silent when its time runs out
+    DefaultChoiceAndSilence {
+        scene: String,
+        line: usize,
+    },
+}
+
+impl fmt::Display for DialogueDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "failed to parse dialogue data: {err}"),
+            Self::DuplicateLabel { scene, label } => {
+                write!(
+                    f,
+                    "label \"{label}\" is used more than once in scene \"{scene}\""
+                )
+            }
+            Self::MissingLabel { scene, label } => {
+                write!(f, "label \"{label}\" does not exist in scene \"{scene}\"")
+            }
+            Self::MissingScene { scene } => write!(f, "scene \"{scene}\" does not exist"),
+            Self::DuplicateId { id, first, second } => {
+                write!(f, "id \"{id}\" is used by both {first} and {second}")
+            }
+            Self::InvalidCondition {
+                scene,
+                condition,
+                message,
+            } => write!(
+                f,
+                "invalid condition \"{condition}\" in scene \"{scene}\": {message}"
+            ),
+            Self::DefaultChoiceAndSilence { scene, line } => write!(
+                f,
+                "line {line} in scene \"{scene}\" has both a default_choice and a silence outcome"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DialogueDataError {}
+
+impl DialogueData {
+    /// parses the data and resolves every `next_label` into a line index
+    pub fn from_json(json: &str) -> Result<Self, DialogueDataError> {
+        let mut data: DialogueData =
+            serde_json::from_str(json).map_err(DialogueDataError::Parse)?;
+        data.resolve_labels()?;
+        data.validate_ids()?;
+        data.validate_conditions()?;
+        data.validate_timeouts()?;
+        Ok(data)
+    }
+
+    pub fn validate_timeouts(&self) -> Result<(), DialogueDataError> {
+        for (scene_name, scene) in &self.scenes {
+            for (index, line) in scene.lines.iter().enumerate() {
+                if line.default_choice.is_some() && line.silence.is_some() {
+                    return Err(DialogueDataError::DefaultChoiceAndSilence {
+                        scene: scene_name.clone(),
+                        line: index,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_conditions(&self) -> Result<(), DialogueDataError> {
+        for (scene_name, scene) in &self.scenes {
+            let conditions = scene.lines.iter().flat_map(|line| {
+                let choice_conditions = line.choices.iter().flatten().map(|c| &c.condition);
+                std::iter::once(&line.condition).chain(choice_conditions)
+            });
+
+            for condition in conditions.flatten() {
+                if let Err(message) = conditions::parse(condition) {
+                    return Err(DialogueDataError::InvalidCondition {
+                        scene: scene_name.clone(),
+                        condition: condition.clone(),
+                        message,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// every title, line, variant, choice and echoed player line needs an id of its own,
+    /// since they share the string tables and voice directories
+    pub fn validate_ids(&self) -> Result<(), DialogueDataError> {
+        let mut scene_names: Vec<&String> = self.scenes.keys().collect();
+        scene_names.sort();
+
+        let mut ids: HashMap<String, String> = HashMap::new();
+        let mut claim = |id: String, location: String| match ids.get(&id) {
+            Some(first) => Err(DialogueDataError::DuplicateId {
+                id,
+                first: first.clone(),
+                second: location,
+            }),
+            None => {
+                ids.insert(id, location);
+                Ok(())
+            }
+        };
+
+        for scene_name in scene_names {
+            let scene = &self.scenes[scene_name];
+            if scene.title.is_some() {
+                claim(
+                    DialogueScene::title_id(scene_name),
+                    format!("{scene_name} title"),
+                )?;
+            }
+
+            for (index, line) in scene.lines.iter().enumerate() {
+                let location = format!("{scene_name}:{index}");
+                let line_id = line.text_id(scene_name, index);
+                claim(line_id.clone(), location.clone())?;
+
+                for n in 0..line.variants.as_ref().map_or(0, Vec::len) {
+                    claim(
+                        format!("{line_id}.variant.{n}"),
+                        format!("{location} variant {n}"),
+                    )?;
+                }
+
+                for (n, choice) in line.choices.iter().flatten().enumerate() {
+                    let choice_id = choice.text_id(&line_id, n);
+                    if choice.player_text.is_some() {
+                        claim(
+                            format!("{choice_id}.player_text"),
+                            format!("{location} choice {n} player text"),
+                        )?;
+                    }
+                    claim(choice_id, format!("{location} choice {n}"))?;
+                }
+
+                if line.silence.is_some() {
+                    claim(format!("{line_id}.silence"), format!("{location} silence"))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn resolve_labels(&mut self) -> Result<(), DialogueDataError> {
+        let mut labels: HashMap<String, HashMap<String, usize>> = HashMap::new();
+        for (scene_name, scene) in &self.scenes {
+            let scene_labels = labels.entry(scene_name.clone()).or_default();
+            for (i, line) in scene.lines.iter().enumerate() {
+                if let Some(label) = &line.label
+                    && scene_labels.insert(label.clone(), i).is_some()
+                {
+                    return Err(DialogueDataError::DuplicateLabel {
+                        scene: scene_name.clone(),
+                        label: label.clone(),
+                    });
+                }
+            }
+        }
+
+        for (scene_name, scene) in self.scenes.iter_mut() {
+            for line in scene.lines.iter_mut() {
+                if let Some(next_line) =
+                    resolve_label(&labels, scene_name, &line.next_scene, &line.next_label)?
+                {
+                    line.next_line = Some(next_line);
+                }
+
+                if let Some(call) = &line.call
+                    && !labels.contains_key(&call.scene)
+                {
+                    return Err(DialogueDataError::MissingScene {
+                        scene: call.scene.clone(),
+                    });
+                }
+
+                for choice in line.choices.iter_mut().flatten() {
+                    if let Some(next_line) =
+                        resolve_label(&labels, scene_name, &choice.next_scene, &choice.next_label)?
+                    {
+                        choice.next_line = Some(next_line);
+                    }
+                }
+
+                if let Some(silence) = &mut line.silence
+                    && let Some(next_line) = resolve_label(
+                        &labels,
+                        scene_name,
+                        &silence.next_scene,
+                        &silence.next_label,
+                    )?
+                {
+                    silence.next_line = Some(next_line);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// a label is looked up in the scene being jumped to, or the current one without a jump
+fn resolve_label(
+    labels: &HashMap<String, HashMap<String, usize>>,
+    scene_name: &str,
+    next_scene: &Option<String>,
+    next_label: &Option<String>,
+) -> Result<Option<usize>, DialogueDataError> {
+    let target_scene = next_scene.as_deref().unwrap_or(scene_name);
+    let scene_labels = labels
+        .get(target_scene)
+        .ok_or_else(|| DialogueDataError::MissingScene {
+            scene: target_scene.to_string(),
+        })?;
+
+    let Some(label) = next_label else {
+        return Ok(None);
+    };
+
+    match scene_labels.get(label) {
+        Some(&index) => Ok(Some(index)),
+        None => Err(DialogueDataError::MissingLabel {
+            scene: target_scene.to_string(),
+            label: label.clone(),
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DialogueChoice {
+    /// key in the string tables, see [`DialogueChoice::text_id`]
+    pub id: Option<String>,
     pub text: String,
     #[serde(default)]
     pub player_text: Option<String>,
     pub player_text_auto_time: Option<f32>,
     pub next_scene: Option<String>,
     pub next_line: Option<usize>,
+    /// resolved into `next_line` when the data is loaded
+    pub next_label: Option<String>,
+    pub triggers: Option<Vec<DialogueTrigger>>,
+    /// hidden once it has been picked
+    #[serde(default)]
+    pub once: bool,
+    /// never dimmed, for options like "goodbye" that are meant to be picked repeatedly
+    #[serde(default)]
+    pub sticky: bool,
+    /// only shown when every other choice has been used up
+    #[serde(default)]
+    pub fallback: bool,
+    /// hidden unless this evaluates to true, see [`conditions`]
+    pub condition: Option<String>,
+    /// direction for the voice actor reading `player_text`, or the choice text when it's
+    /// echoed without one, only used by the recording scripts
+    pub notes: Option<String>,
+}
+
+/// what happens when a timed choice runs out without an answer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SilenceOutcome {
+    pub player_text: Option<String>,
+    pub player_text_auto_time: Option<f32>,
+    pub next_scene: Option<String>,
+    pub next_line: Option<usize>,
+    pub next_label: Option<String>,
     pub triggers: Option<Vec<DialogueTrigger>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl From<SilenceOutcome> for DialogueChoice {
+    fn from(silence: SilenceOutcome) -> Self {
+        DialogueChoice {
+            text: String::new(),
+            player_text: silence.player_text,
+            player_text_auto_time: silence.player_text_auto_time,
+            next_scene: silence.next_scene,
+            next_line: silence.next_line,
+            next_label: silence.next_label,
+            triggers: silence.triggers,
+            ..default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DialogueLine {
+    /// key in the string tables, see [`DialogueLine::text_id`]
+    pub id: Option<String>,
+    /// name other lines and choices can jump to with `next_label`
+    pub label: Option<String>,
+    /// skipped unless this evaluates to true, see [`conditions`]
+    pub condition: Option<String>,
+    #[serde(default)]
     pub speaker: String,
+    /// lines without text aren't shown, they only run their triggers and directives
+    #[serde(default)]
     pub text: String,
+    /// alternative texts picked from according to `variant_mode`, replacing `text`
+    pub variants: Option<Vec<String>>,
+    #[serde(default)]
+    pub variant_mode: VariantMode,
     pub auto_time: Option<f32>,
     pub camera_target: Option<String>,
+    /// recorded voice-over, see [`voice::voice_path`] for where it's looked up without one
+    pub voice: Option<String>,
+    /// direction for the voice actor, only used by the recording scripts
+    pub notes: Option<String>,
     pub choices: Option<Vec<DialogueChoice>>,
+    /// seconds the player has to pick one of `choices`
+    pub choice_timeout: Option<f32>,
+    /// index into `choices` picked when `choice_timeout` runs out
+    pub default_choice: Option<usize>,
+    /// what happens when `choice_timeout` runs out and not answering is its own outcome,
+    /// a line can't have both this and `default_choice`
+    pub silence: Option<SilenceOutcome>,
     pub triggers: Option<Vec<DialogueTrigger>>,
+    /// scene to continue with once this line is done, when there are no choices
+    pub next_scene: Option<String>,
+    /// line to continue with instead of the one after it, when there are no choices
+    pub next_line: Option<usize>,
+    /// resolved into `next_line` when the data is loaded
+    pub next_label: Option<String>,
+    /// ends the dialogue after this line, even if the scene has more lines
+    #[serde(default)]
+    pub end: bool,
+    /// runs another scene after this line, then carries on where this line would have gone
+    pub call: Option<SceneCall>,
+}
+
+impl DialogueLine {
+    pub fn is_silent(&self) -> bool {
+        self.text.is_empty() && self.variants.is_none() && self.choices.is_none()
+    }
+
+    /// the explicit `id`, or `scene.index` for lines without one. variants are looked up
+    /// as `<id>.variant.<n>`, the silence outcome's player text as `<id>.silence`
+    pub fn text_id(&self, scene: &str, index: usize) -> String {
+        self.id
+            .clone()
+            .unwrap_or_else(|| format!("{scene}.{index}"))
+    }
+
+    /// changes whenever what is said changes, to find lines that need re-translating
+    /// or re-recording
+    pub fn content_hash(&self) -> u64 {
+        let variants = self.variants.iter().flatten().map(String::as_str);
+        stable_hash(
+            [self.speaker.as_str(), &self.text]
+                .into_iter()
+                .chain(variants),
+        )
+    }
+}
+
+impl DialogueChoice {
+    /// the explicit `id`, or `<line id>.choice.<n>` for choices without one. the player
+    /// text is looked up as `<id>.player_text`
+    pub fn text_id(&self, line_id: &str, index: usize) -> String {
+        self.id
+            .clone()
+            .unwrap_or_else(|| format!("{line_id}.choice.{index}"))
+    }
+
+    pub fn content_hash(&self) -> u64 {
+        stable_hash([
+            self.text.as_str(),
+            self.player_text.as_deref().unwrap_or_default(),
+        ])
+    }
+}
+
+/// FNV-1a, which unlike the std hasher gives the same hash on every build and platform
+fn stable_hash<'a>(fields: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for field in fields {
+        // the separator keeps ["ab", "c"] and ["a", "bc"] apart
+        for byte in field.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantMode {
+    /// any variant, repeats allowed
+    #[default]
+    Random,
+    /// each variant in order, then keeps showing the last one
+    Sequence,
+    /// each variant in order, then starts over
+    Cycle,
+    /// every variant once in a random order before any of them repeats
+    Shuffle,
+    /// the first variant on the first visit to the scene, the second on the next visit and so
+    /// on, sticking to the last one
+    Visits,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneCall {
+    pub scene: String,
+    /// bound as scene-local variables while the called scene runs
+    #[serde(default)]
+    pub args: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -62,27 +617,167 @@ pub struct DialoguePlugin;
 impl Plugin for DialoguePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(EguiPlugin)
+            .add_audio_source::<voice::Blip>()
+            .init_state::<DialogueMode>()
             .add_event::<StartDialogueEvent>()
+            .add_event::<CancelDialogueEvent>()
             .add_event::<NextDialogueEvent>()
             .add_event::<ToggleAutoEvent>()
+            .add_event::<ToggleSkipEvent>()
             .add_event::<MakeChoiceEvent>()
             .add_event::<ActionTriggerEvent>()
             .add_event::<ResetSceneEvent>()
+            .add_event::<RebindActionEvent>()
+            .add_event::<RollbackEvent>()
+            .add_event::<ExportLogEvent>()
+            .add_event::<DialogueStartedEvent>()
+            .add_event::<LineShownEvent>()
+            .add_event::<ChoicesPresentedEvent>()
+            .add_event::<ChoiceMadeEvent>()
+            .add_event::<SceneChangedEvent>()
+            .add_event::<DialogueEndedEvent>()
             .add_systems(Startup, setup_dialogue)
             .add_systems(
                 Update,
                 (
                     handle_start_dialogue,
+                    handle_cancel_dialogue.before(handle_start_dialogue),
                     handle_next_dialogue,
                     handle_choice,
+                    handle_choice_timeout.run_if(in_state(DialogueMode::Choosing)),
+                    handle_rollback,
                     handle_input,
                     handle_toggle_auto,
+                    handle_toggle_skip,
+                    handle_rebind_action,
                     dialogue_ui,
                     dialogue_log_ui,
-                    handle_auto_dialogue,
+                    handle_auto_dialogue
+                        .before(handle_next_dialogue)
+                        .run_if(in_dialogue()),
                     handle_reset_scene,
+                    handle_locale_change,
+                    handle_export_log,
+                    cleanup_failed_voice,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    handle_move_to_trigger,
+                    // the dialogue applies its own triggers as it fires them, this is for
+                    // the ones game code sends, which shouldn't land on top of what the
+                    // dialogue changes later in the frame
+                    handle_set_variable_trigger
+                        .before(handle_next_dialogue)
+                        .before(handle_choice)
+                        .before(handle_rollback),
+                    handle_start_dialogue_trigger,
+                    process_movement,
+                    update_dialogue_mode.after(process_movement),
+                    update_typewriter.after(handle_next_dialogue),
                 ),
             )
-            .add_systems(Update, (handle_move_to_trigger, process_movement));
+            .add_systems(Last, save_progress_on_exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_default_choice_with_a_silence_outcome() {
+        let err = DialogueData::from_json(
+            r#"{"scenes": {"bar": [
+                {"speaker": "Joe", "text": "well?", "choice_timeout": 5,
+                 "default_choice": 0, "silence": {"player_text": "..."},
+                 "choices": [{"text": "yes"}]}
+            ]}}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            DialogueDataError::DefaultChoiceAndSilence { line: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn titles_share_the_id_namespace() {
+        let err = DialogueData::from_json(
+            r#"{"scenes": {"bar": {"title": "The Bar", "lines": [
+                {"id": "bar.title", "speaker": "Joe", "text": "hi"}
+            ]}}}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, DialogueDataError::DuplicateId { .. }));
+    }
+
+    fn load(scenes: &str) -> Result<DialogueData, DialogueDataError> {
+        DialogueData::from_json(&format!(r#"{{"scenes": {scenes}}}"#))
+    }
+
+    #[test]
+    fn resolves_labels_in_other_scenes() {
+        let data = load(
+            r#"{
+                "bar": [{"speaker": "Joe", "text": "off we go", "next_scene": "street",
+                         "next_label": "outside"}],
+                "street": [
+                    {"speaker": "Joe", "text": "skipped"},
+                    {"label": "outside", "speaker": "Joe", "text": "here"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(data.scenes["bar"].lines[0].next_line, Some(1));
+    }
+
+    #[test]
+    fn rejects_duplicate_labels() {
+        let err = load(
+            r#"{"bar": [
+                {"label": "start", "speaker": "Joe", "text": "a"},
+                {"label": "start", "speaker": "Joe", "text": "b"}
+            ]}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            DialogueDataError::DuplicateLabel { scene, label } if scene == "bar" && label == "start"
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_labels() {
+        let err = load(
+            r#"{
+                "bar": [{"speaker": "Joe", "text": "a", "next_scene": "street",
+                         "next_label": "nowhere"}],
+                "street": [{"label": "outside", "speaker": "Joe", "text": "b"}]
+            }"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            DialogueDataError::MissingLabel { scene, label } if scene == "street" && label == "nowhere"
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_scenes() {
+        for scenes in [
+            r#"{"bar": [{"speaker": "Joe", "text": "a", "next_scene": "moon"}]}"#,
+            r#"{"bar": [{"speaker": "Joe", "text": "a", "call": {"scene": "moon"}}]}"#,
+            r#"{"bar": [{"speaker": "Joe", "text": "a", "choices": [
+                {"text": "b", "next_scene": "moon"}
+            ]}]}"#,
+        ] {
+            let err = load(scenes).unwrap_err();
+            assert!(
+                matches!(&err, DialogueDataError::MissingScene { scene } if scene == "moon"),
+                "{err}"
+            );
+        }
     }
 }
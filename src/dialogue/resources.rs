@@ -1,16 +1,777 @@
-use bevy::prelude::*;
+use std::{
+    borrow::Cow,
+    fmt::Write,
+    fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use crate::dialogue::DialogueLogEntry;
+use std::collections::{BTreeMap, VecDeque};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::dialogue::{
+    DialogueChoice, DialogueLine, DialogueLogEntry, LogContent,
+    components::{CallFrame, ChoiceKey, DialogueManager, LineKey, VariantState},
+    events::StartDialogueEvent,
+};
+
+pub const READ_LINES_PATH: &str = "saves/read_lines.json";
+pub const SAVE_PATH: &str = "saves/dialogue.json";
+pub const LOCALES_DIR: &str = "assets/locales";
+/// the last session's log, written on exit
+pub const TRANSCRIPT_PATH: &str = "saves/transcript.json";
+pub const TRANSCRIPTS_DIR: &str = "saves/transcripts";
+/// entries the log keeps unless DIALOGUE_LOG_CAPACITY says otherwise
+pub const DEFAULT_LOG_CAPACITY: usize = 500;
+/// lines that can be rolled back unless DIALOGUE_ROLLBACK_DEPTH says otherwise
+pub const DEFAULT_ROLLBACK_DEPTH: usize = 100;
+/// the language the text in dialogue.json is written in
+pub const SOURCE_LOCALE: &str = "en";
+/// debug locale that garbles the source text, so clipped text and strings that never go
+/// through the string tables stand out before real translations exist
+pub const PSEUDO_LOCALE: &str = "pseudo";
+
+/// characters the typewriter reveals per second
+pub const TYPEWRITER_SPEED: f32 = 40.0;
+
+/// settings and progress kept as a JSON file between sessions
+pub trait JsonFile: Serialize + DeserializeOwned + Default {
+    /// what the file holds, for the warning when it can't be parsed
+    const DESCRIPTION: &str;
+
+    /// falls back to the default if the file is missing or can't be parsed
+    fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+
+        serde_json::from_str(&contents).unwrap_or_else(|err| {
+            warn!("invalid {} in {}: {err}", Self::DESCRIPTION, path.display());
+            Self::default()
+        })
+    }
+
+    fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        write_file(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// writes the file, creating the directories it's in first
+pub fn write_file(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
 
 #[derive(Resource)]
 pub struct DialogueState {
     pub active: bool,
     pub current_text: String,
+    /// counts the lines shown, so a new line can be told apart from the current one being
+    /// re-rendered in another locale, even when their text is the same
+    pub shown_lines: usize,
     pub current_speaker: String,
+    /// the running scene's title, translated along with `current_text`
+    pub current_title: Option<String>,
+}
+
+/// how much of the current line the dialogue box has revealed so far
+#[derive(Resource)]
+pub struct Typewriter {
+    /// the [`DialogueState::shown_lines`] count of the line being revealed
+    pub line: usize,
+    pub text: String,
+    /// in characters, not bytes
+    pub revealed: usize,
+    pub timer: Timer,
+}
+
+impl Default for Typewriter {
+    fn default() -> Self {
+        Self {
+            line: 0,
+            text: String::new(),
+            revealed: 0,
+            timer: Timer::from_seconds(1.0 / TYPEWRITER_SPEED, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Typewriter {
+    pub fn start(&mut self, line: usize, text: &str) {
+        self.line = line;
+        self.text = text.to_string();
+        self.revealed = 0;
+        self.timer.reset();
+    }
+
+    /// swaps in the same line in another locale, keeping as much of it revealed
+    pub fn retext(&mut self, text: &str) {
+        let finished = !self.is_revealing();
+        self.text = text.to_string();
+        if finished {
+            self.finish();
+        } else {
+            self.revealed = self.revealed.min(self.text.chars().count());
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.revealed = self.text.chars().count();
+    }
+
+    pub fn is_revealing(&self) -> bool {
+        self.revealed < self.text.chars().count()
+    }
+
+    /// reveals up to `count` more characters and returns them
+    pub fn reveal(&mut self, count: usize) -> &str {
+        let start = self.byte_offset(self.revealed);
+        self.revealed = (self.revealed + count).min(self.text.chars().count());
+        &self.text[start..self.byte_offset(self.revealed)]
+    }
+
+    pub fn visible(&self) -> &str {
+        &self.text[..self.byte_offset(self.revealed)]
+    }
+
+    fn byte_offset(&self, chars: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(chars)
+            .map_or(self.text.len(), |(offset, _)| offset)
+    }
+}
+
+/// the language dialogue text is shown in, changing it re-renders the current line
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct Locale(pub String);
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self(std::env::var("DIALOGUE_LOCALE").unwrap_or_else(|_| SOURCE_LOCALE.to_string()))
+    }
+}
+
+/// translated text per locale, keyed by the ids from [`DialogueLine::text_id`] and
+/// [`crate::dialogue::DialogueChoice::text_id`]
+#[derive(Resource, Default)]
+pub struct StringTables {
+    pub tables: HashMap<String, HashMap<String, Translation>>,
 }
 
+/// a string table entry, either plain text or, when written by the import tool, the text
+/// together with the source it was translated from so changed lines can be found
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Translation {
+    Text(String),
+    Tracked { text: String, source: String },
+}
+
+impl Translation {
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Text(text) | Self::Tracked { text, .. } => text,
+        }
+    }
+
+    /// `None` for hand written entries, which are always assumed to be up to date
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            Self::Text(_) => None,
+            Self::Tracked { source, .. } => Some(source),
+        }
+    }
+}
+
+impl StringTables {
+    /// reads every `<locale>.json` in `dir`, a missing directory just means no translations
+    pub fn load(dir: impl AsRef<Path>) -> Self {
+        let mut tables = HashMap::new();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Self { tables };
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            match Self::load_table(&path) {
+                Ok(table) => {
+                    tables.insert(locale.to_string(), table);
+                }
+                Err(err) => warn!("invalid string table {}: {err}", path.display()),
+            }
+        }
+
+        Self { tables }
+    }
+
+    pub fn load_table(path: impl AsRef<Path>) -> Result<HashMap<String, Translation>, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        serde_json::from_str(&contents).map_err(|err| err.to_string())
+    }
+
+    /// writes the entries sorted by id, so re-importing gives small diffs
+    pub fn save_table(
+        path: impl AsRef<Path>,
+        table: &HashMap<String, Translation>,
+    ) -> io::Result<()> {
+        let sorted: BTreeMap<_, _> = table.iter().collect();
+        write_file(path, serde_json::to_string_pretty(&sorted)? + "\n")
+    }
+
+    /// the translation of `id`, falling back to `source` with a warning when there is none
+    pub fn translate<'a>(&'a self, locale: &str, id: &str, source: &'a str) -> Cow<'a, str> {
+        if locale == SOURCE_LOCALE {
+            return Cow::Borrowed(source);
+        }
+
+        if locale == PSEUDO_LOCALE {
+            return Cow::Owned(pseudo_localize(source));
+        }
+
+        match self.tables.get(locale).and_then(|table| table.get(id)) {
+            Some(translation) => Cow::Borrowed(translation.text()),
+            None => {
+                warn!("missing {locale} translation for \"{id}\"");
+                Cow::Borrowed(source)
+            }
+        }
+    }
+
+    /// the source locale followed by every locale with a string table
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self
+            .tables
+            .keys()
+            .filter(|locale| *locale != SOURCE_LOCALE)
+            .cloned()
+            .collect();
+        locales.sort();
+        locales.insert(0, SOURCE_LOCALE.to_string());
+        locales.push(PSEUDO_LOCALE.to_string());
+        locales
+    }
+}
+
+/// swaps letters for accented look-alikes, pads the text by about 35% and wraps it in
+/// markers, leaving `{name}` placeholders alone so interpolation still works
+pub fn pseudo_localize(text: &str) -> String {
+    const MARKER_OPEN: &str = "[!! ";
+    const MARKER_CLOSE: &str = " !!]";
+
+    let mut result = String::from(MARKER_OPEN);
+    let mut in_placeholder = false;
+    let mut letters = 0;
+
+    for c in text.chars() {
+        match c {
+            '{' => in_placeholder = true,
+            '}' => in_placeholder = false,
+            _ => {}
+        }
+
+        if in_placeholder || !c.is_ascii_alphabetic() {
+            result.push(c);
+            continue;
+        }
+
+        letters += 1;
+        result.push(match c {
+            'a' => 'á',
+            'c' => 'ç',
+            'd' => 'ð',
+            'e' => 'é',
+            'g' => 'ĝ',
+            'h' => 'ĥ',
+            'i' => 'î',
+            'j' => 'ĵ',
+            'k' => 'ķ',
+            'l' => 'ļ',
+            'n' => 'ñ',
+            'o' => 'ö',
+            's' => 'š',
+            't' => 'ţ',
+            'u' => 'ü',
+            'w' => 'ŵ',
+            'y' => 'ý',
+            'z' => 'ž',
+            'A' => 'Å',
+            'C' => 'Ç',
+            'D' => 'Ð',
+            'E' => 'É',
+            'G' => 'Ĝ',
+            'H' => 'Ĥ',
+            'I' => 'Î',
+            'J' => 'Ĵ',
+            'L' => 'Ļ',
+            'N' => 'Ñ',
+            'O' => 'Ö',
+            'S' => 'Š',
+            'T' => 'Ţ',
+            'U' => 'Û',
+            'W' => 'Ŵ',
+            'Y' => 'Ý',
+            'Z' => 'Ž',
+            other => other,
+        });
+    }
+
+    // translations tend to run 30-40% longer than english
+    let padding = (text.chars().count() * 35).div_ceil(100);
+    if letters > 0 && padding > 0 {
+        result.push(' ');
+        result.extend(std::iter::repeat_n('~', padding));
+    }
+
+    result.push_str(MARKER_CLOSE);
+    result
+}
+
+/// dialogues waiting for the running one to finish, started first come first served
 #[derive(Resource, Default)]
+pub struct DialogueQueue {
+    pub pending: VecDeque<StartDialogueEvent>,
+}
+
+/// the transcript of the dialogue so far, dropping the oldest entries past `capacity`
+#[derive(Resource)]
 pub struct DialogueLog {
-    pub entries: Vec<DialogueLogEntry>,
+    pub entries: VecDeque<DialogueLogEntry>,
+    pub capacity: usize,
+    /// entries dropped to stay within `capacity`, so positions from [`DialogueLog::total_len`]
+    /// stay valid for rollback
+    pub dropped: usize,
     pub show_log: bool,
 }
+
+impl Default for DialogueLog {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: std::env::var("DIALOGUE_LOG_CAPACITY")
+                .ok()
+                .and_then(|capacity| capacity.parse().ok())
+                .unwrap_or(DEFAULT_LOG_CAPACITY),
+            dropped: 0,
+            show_log: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Markdown,
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Markdown => "md",
+            LogFormat::Text => "txt",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+impl DialogueLog {
+    pub fn push(&mut self, scene: &str, line: Option<usize>, content: LogContent) {
+        self.entries.push_back(DialogueLogEntry {
+            time: SystemTime::now(),
+            scene: scene.to_string(),
+            line,
+            content,
+        });
+
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    /// every entry ever pushed, including the dropped ones
+    pub fn total_len(&self) -> usize {
+        self.dropped + self.entries.len()
+    }
+
+    /// drops the entries pushed after the log was `len` long
+    pub fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len.saturating_sub(self.dropped));
+    }
+
+    pub fn export(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Markdown => self.export_markdown(),
+            LogFormat::Text => self.export_text(),
+            LogFormat::Json => self.export_json(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: LogFormat) -> io::Result<()> {
+        write_file(path, self.export(format))
+    }
+
+    fn export_markdown(&self) -> String {
+        let mut markdown = String::from("# Dialogue transcript\n\n");
+        if self.dropped > 0 {
+            writeln!(markdown, "_{} earlier entries dropped_\n", self.dropped).unwrap();
+        }
+
+        for entry in &self.entries {
+            let location = entry.location();
+            let time = format_timestamp(entry.time);
+            match &entry.content {
+                LogContent::Line { speaker, text } => {
+                    writeln!(markdown, "- `{time}` `{location}` **{speaker}:** {text}").unwrap();
+                }
+                LogContent::Choices {
+                    options,
+                    selected_index,
+                } => {
+                    let outcome = if selected_index.is_some() {
+                        "choice"
+                    } else {
+                        "choice, timed out"
+                    };
+                    writeln!(markdown, "- `{time}` `{location}` _{outcome}_").unwrap();
+                    for (i, choice) in options.iter().enumerate() {
+                        let mark = if Some(i) == *selected_index { "x" } else { " " };
+                        writeln!(markdown, "  - [{mark}] {}", choice_label(choice)).unwrap();
+                    }
+                }
+            }
+        }
+        markdown
+    }
+
+    fn export_text(&self) -> String {
+        let mut text = String::new();
+        if self.dropped > 0 {
+            writeln!(text, "({} earlier entries dropped)", self.dropped).unwrap();
+        }
+
+        for entry in &self.entries {
+            let prefix = format!("[{}] {}", format_timestamp(entry.time), entry.location());
+            match &entry.content {
+                LogContent::Line {
+                    speaker,
+                    text: line,
+                } => {
+                    writeln!(text, "{prefix} {speaker}: {line}").unwrap();
+                }
+                LogContent::Choices {
+                    options,
+                    selected_index,
+                } => {
+                    let outcome = if selected_index.is_some() {
+                        "choice"
+                    } else {
+                        "choice (timed out)"
+                    };
+                    writeln!(text, "{prefix} {outcome}").unwrap();
+                    for (i, choice) in options.iter().enumerate() {
+                        let mark = if Some(i) == *selected_index { '>' } else { ' ' };
+                        writeln!(text, "    {mark} {}", choice_label(choice)).unwrap();
+                    }
+                }
+            }
+        }
+        text
+    }
+
+    fn export_json(&self) -> String {
+        let entries: Vec<serde_json::Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut value = serde_json::json!({
+                    "time": format_timestamp(entry.time),
+                    "scene": entry.scene,
+                    "line": entry.line,
+                });
+                let content = match &entry.content {
+                    LogContent::Line { speaker, text } => serde_json::json!({
+                        "type": "line",
+                        "speaker": speaker,
+                        "text": text,
+                    }),
+                    LogContent::Choices {
+                        options,
+                        selected_index,
+                    } => serde_json::json!({
+                        "type": "choices",
+                        "options": options.iter().map(choice_label).collect::<Vec<_>>(),
+                        "selected": selected_index,
+                    }),
+                };
+                value
+                    .as_object_mut()
+                    .unwrap()
+                    .extend(content.as_object().unwrap().clone());
+                value
+            })
+            .collect();
+
+        let transcript = serde_json::json!({
+            "dropped": self.dropped,
+            "entries": entries,
+        });
+        serde_json::to_string_pretty(&transcript).unwrap() + "\n"
+    }
+}
+
+/// which log entries the log window shows, and the one whose location it's showing
+#[derive(Resource, Default)]
+pub struct LogView {
+    pub search: String,
+    pub speaker: Option<String>,
+    pub scene: Option<String>,
+    /// counted like [`DialogueLog::total_len`], so it stays on its entry as old ones drop
+    pub selected: Option<usize>,
+}
+
+impl LogView {
+    pub fn shows(&self, entry: &DialogueLogEntry) -> bool {
+        if self
+            .speaker
+            .as_deref()
+            .is_some_and(|speaker| speaker != entry.speaker())
+            || self
+                .scene
+                .as_ref()
+                .is_some_and(|scene| *scene != entry.scene)
+        {
+            return false;
+        }
+
+        let search = self.search.trim().to_lowercase();
+        if search.is_empty() {
+            return true;
+        }
+
+        let found = |text: &str| text.to_lowercase().contains(&search);
+        match &entry.content {
+            LogContent::Line { speaker, text } => found(speaker) || found(text),
+            LogContent::Choices { options, .. } => {
+                options.iter().any(|choice| found(choice_label(choice)))
+            }
+        }
+    }
+}
+
+/// what the log shows for a choice, the line the player says rather than the button text
+pub fn choice_label(choice: &DialogueChoice) -> &str {
+    choice.player_text.as_ref().unwrap_or(&choice.text)
+}
+
+/// RFC 3339 in UTC with milliseconds, e.g. `2025-03-14T09:26:53.589Z`
+pub fn format_timestamp(time: SystemTime) -> String {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64);
+    let days = (millis / 86_400_000) as i64;
+    let day_millis = millis % 86_400_000;
+
+    // days since the epoch to a civil date, after Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        day_millis / 3_600_000,
+        day_millis / 60_000 % 60,
+        day_millis / 1000 % 60,
+        day_millis % 1000
+    )
+}
+
+/// every scene line the player has seen, kept across sessions for skip mode
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct ReadLines {
+    pub lines: HashSet<LineKey>,
+}
+
+impl JsonFile for ReadLines {
+    const DESCRIPTION: &str = "read lines";
+}
+
+impl ReadLines {
+    pub fn is_read(&self, scene: &str, line: usize) -> bool {
+        self.lines.contains(&LineKey {
+            scene: scene.to_string(),
+            line,
+        })
+    }
+
+    pub fn mark_read(&mut self, scene: &str, line: usize) {
+        self.lines.insert(LineKey {
+            scene: scene.to_string(),
+            line,
+        });
+    }
+}
+
+/// progress that carries over between dialogues and sessions
+#[derive(Default, Serialize, Deserialize)]
+pub struct DialogueSave {
+    pub scene_visits: HashMap<String, u32>,
+    /// stored as pairs since json object keys have to be strings
+    pub line_visits: Vec<(LineKey, u32)>,
+    pub variables: HashMap<String, serde_json::Value>,
+    pub taken_choices: Vec<ChoiceKey>,
+}
+
+impl JsonFile for DialogueSave {
+    const DESCRIPTION: &str = "dialogue save";
+}
+
+impl DialogueSave {
+    pub fn from_manager(manager: &DialogueManager) -> Self {
+        Self {
+            scene_visits: manager.scene_visits.clone(),
+            line_visits: manager
+                .line_visits
+                .iter()
+                .map(|(key, visits)| (key.clone(), *visits))
+                .collect(),
+            variables: manager.variables.clone(),
+            taken_choices: manager.taken_choices.iter().cloned().collect(),
+        }
+    }
+
+    pub fn apply(self, manager: &mut DialogueManager) {
+        manager.scene_visits = self.scene_visits;
+        manager.line_visits = self.line_visits.into_iter().collect();
+        manager.variables = self.variables;
+        manager.taken_choices = self.taken_choices.into_iter().collect();
+    }
+}
+
+/// everything needed to put the dialogue back to how it was right before a line was shown
+#[derive(Clone)]
+pub struct DialogueSnapshot {
+    pub scene: String,
+    pub line: usize,
+    pub ephemeral_line: Option<DialogueLine>,
+    pub taken_choices: HashSet<ChoiceKey>,
+    pub variables: HashMap<String, serde_json::Value>,
+    pub call_stack: Vec<CallFrame>,
+    pub locals: HashMap<String, serde_json::Value>,
+    pub variant_states: HashMap<LineKey, VariantState>,
+    pub scene_visits: HashMap<String, u32>,
+    pub line_visits: HashMap<LineKey, u32>,
+    pub rng: DialogueRng,
+    pub log_len: usize,
+    pub camera_transform: Option<Transform>,
+    pub actor_transforms: Vec<(Entity, Transform)>,
+}
+
+impl DialogueSnapshot {
+    /// puts the manager back where it was when the snapshot was taken, the rng, log, camera
+    /// and actors live elsewhere
+    pub fn restore(&self, manager: &mut DialogueManager) {
+        manager.current_scene = self.scene.clone();
+        manager.current_line = self.line;
+        manager.ephemeral_line = self.ephemeral_line.clone();
+        manager.taken_choices = self.taken_choices.clone();
+        manager.variables = self.variables.clone();
+        manager.call_stack = self.call_stack.clone();
+        manager.locals = self.locals.clone();
+        manager.variant_states = self.variant_states.clone();
+        manager.scene_visits = self.scene_visits.clone();
+        manager.line_visits = self.line_visits.clone();
+        manager.scene_transitions.clear();
+        manager.fired_triggers.clear();
+        manager.waiting_for_choice = false;
+        manager.current_choices.clear();
+        manager.current_choice_indices.clear();
+        manager.focused_choice = 0;
+        manager.choice_timeout = None;
+        manager.skip_mode = false;
+        manager.ending = false;
+    }
+}
+
+#[derive(Resource)]
+pub struct DialogueHistory {
+    pub snapshots: VecDeque<DialogueSnapshot>,
+    /// how many lines can be rolled back, the oldest snapshots are dropped past this
+    pub max_depth: usize,
+}
+
+impl Default for DialogueHistory {
+    fn default() -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+            max_depth: std::env::var("DIALOGUE_ROLLBACK_DEPTH")
+                .ok()
+                .and_then(|depth| depth.parse().ok())
+                .unwrap_or(DEFAULT_ROLLBACK_DEPTH),
+        }
+    }
+}
+
+impl DialogueHistory {
+    pub fn push(&mut self, snapshot: DialogueSnapshot) {
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > self.max_depth {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+/// drives every random pick in the dialogue, insert a seeded one for deterministic runs
+#[derive(Resource, Clone)]
+pub struct DialogueRng(pub ChaCha8Rng);
+
+impl Default for DialogueRng {
+    fn default() -> Self {
+        Self(ChaCha8Rng::from_entropy())
+    }
+}
+
+impl DialogueRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    /// seeded from `DIALOGUE_SEED` when it's set, which makes random variants repeatable for
+    /// tests, replays and the subtitle export
+    pub fn from_env() -> Self {
+        std::env::var("DIALOGUE_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .map(Self::seeded)
+            .unwrap_or_default()
+    }
+}
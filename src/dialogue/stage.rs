@@ -0,0 +1,55 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::dialogue::components::{Actor, DialogueCamera, DialogueTarget, Speaker};
+
+type CameraFilter = (
+    With<DialogueCamera>,
+    Without<DialogueTarget>,
+    Without<Actor>,
+);
+type TargetFilter = (With<DialogueTarget>, Without<DialogueCamera>);
+type ActorFilter = (With<Actor>, Without<DialogueCamera>);
+
+/// the dialogue camera and the characters it points at
+#[derive(SystemParam)]
+pub struct DialogueStage<'w, 's> {
+    cameras: Query<'w, 's, &'static mut Transform, CameraFilter>,
+    targets: Query<'w, 's, (&'static Transform, &'static Speaker), TargetFilter>,
+    actors: Query<'w, 's, (Entity, &'static Transform), ActorFilter>,
+}
+
+impl DialogueStage<'_, '_> {
+    /// frames the speaker with the given name, if they're on stage
+    pub fn focus(&mut self, speaker_name: &str) {
+        let Ok(mut camera_transform) = self.cameras.get_single_mut() else {
+            return;
+        };
+
+        if let Some((target_transform, _)) = self
+            .targets
+            .iter()
+            .find(|(_, speaker)| speaker.name == speaker_name)
+        {
+            let target_pos = target_transform.translation;
+            camera_transform.translation = target_pos + Vec3::new(0.0, 2.0, 5.0);
+            camera_transform.look_at(target_pos + Vec3::new(0.0, 1.0, 0.0), Vec3::Y);
+        }
+    }
+
+    pub fn camera_transform(&self) -> Option<Transform> {
+        self.cameras.get_single().ok().copied()
+    }
+
+    pub fn set_camera_transform(&mut self, transform: Transform) {
+        if let Ok(mut camera_transform) = self.cameras.get_single_mut() {
+            *camera_transform = transform;
+        }
+    }
+
+    pub fn actor_transforms(&self) -> Vec<(Entity, Transform)> {
+        self.actors
+            .iter()
+            .map(|(entity, transform)| (entity, *transform))
+            .collect()
+    }
+}
@@ -0,0 +1,20 @@
+use bevy::prelude::*;
+
+/// what the dialogue is doing, for run conditions and `OnEnter`/`OnExit` schedules
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DialogueMode {
+    #[default]
+    Idle,
+    Talking,
+    Choosing,
+    /// an actor is still carrying out a trigger, like walking to a `move_to` target
+    WaitingForAction,
+}
+
+/// run condition for systems that should only run while a dialogue is going on,
+/// or be frozen during one with `not(in_dialogue())`
+pub fn in_dialogue() -> impl FnMut(Option<Res<State<DialogueMode>>>) -> bool + Clone {
+    |mode: Option<Res<State<DialogueMode>>>| {
+        mode.is_some_and(|mode| *mode.get() != DialogueMode::Idle)
+    }
+}
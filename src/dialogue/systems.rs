@@ -1,183 +1,509 @@
+use std::collections::BTreeSet;
+
 use bevy::prelude::*;
 use bevy_egui::{EguiContexts, egui};
+use rand::Rng;
 
 use crate::ORIGINAL_JOE_POSITION;
-use crate::PLAYER_NAME;
 use crate::dialogue::components::*;
 use crate::dialogue::events::*;
+use crate::dialogue::input::*;
 use crate::dialogue::resources::*;
+use crate::dialogue::stage::*;
+use crate::dialogue::states::*;
+use crate::dialogue::voice::*;
 use crate::dialogue::*;
 
 pub fn setup_dialogue(mut commands: Commands) {
     let dialogue_json = include_str!("../../assets/dialogue.json");
 
-    let dialogue_data: DialogueData = serde_json::from_str(dialogue_json).unwrap();
+    let dialogue_data = DialogueData::from_json(dialogue_json)
+        .unwrap_or_else(|err| panic!("invalid dialogue.json: {err}"));
 
     commands.insert_resource(DialogueState {
         active: false,
         current_text: String::new(),
+        shown_lines: 0,
         current_speaker: String::new(),
+        current_title: None,
     });
 
     commands.insert_resource(DialogueLog::default());
 
+    commands.insert_resource(LogView::default());
+
+    commands.insert_resource(Typewriter::default());
+
+    commands.insert_resource(InputMap::load_or_default(INPUT_SETTINGS_PATH));
+
+    commands.insert_resource(ReadLines::load_or_default(READ_LINES_PATH));
+
+    commands.insert_resource(DialogueHistory::default());
+
+    commands.insert_resource(DialogueQueue::default());
+
+    commands.insert_resource(Locale::default());
+
+    commands.insert_resource(StringTables::load(LOCALES_DIR));
+
+    commands.insert_resource(DialogueRng::from_env());
+
+    let mut manager = DialogueManager::new(dialogue_data);
+    DialogueSave::load_or_default(SAVE_PATH).apply(&mut manager);
+
     commands.spawn((
-        DialogueManager {
-            original_camera_transform: None,
-            current_scene: String::new(),
-            current_line: 0,
-            dialogue_data,
-            auto_mode: false,
-            waiting_for_choice: false,
-            current_choices: Vec::new(),
-            ephemeral_line: None,
-        },
+        manager,
         DialogueTimer(Timer::from_seconds(0.0, TimerMode::Once)),
     ));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_start_dialogue(
+    mut commands: Commands,
     mut events: EventReader<StartDialogueEvent>,
     mut dialogue_query: Query<&mut DialogueManager>,
     mut dialogue_state: ResMut<DialogueState>,
+    mut history: ResMut<DialogueHistory>,
+    mut queue: ResMut<DialogueQueue>,
+    read_lines: Res<ReadLines>,
     mut next_events: EventWriter<NextDialogueEvent>,
+    mut action_events: EventWriter<ActionTriggerEvent>,
     camera_query: Query<&Transform, With<DialogueCamera>>,
 ) {
     for event in events.read() {
-        let camera_transform = camera_query.get_single().unwrap();
         let mut manager = dialogue_query.get_single_mut().unwrap();
-        manager.original_camera_transform = Some(*camera_transform);
-        manager.current_scene = event.scene_name.clone();
-        manager.current_line = 0;
+
+        if dialogue_state.active {
+            if event.priority < manager.priority {
+                info!(
+                    "dropped dialogue \"{}\", a more important one is running",
+                    event.scene_name
+                );
+                continue;
+            }
+
+            if event.priority == manager.priority {
+                queue.pending.push_back(event.clone());
+                continue;
+            }
+
+            // the camera stays where it is, `original_camera_transform` still holds the
+            // position from before the interrupted dialogue
+            manager.leave_dialogue();
+            send_dialogue_events(&mut manager, &mut commands, &mut action_events);
+            end_dialogue(
+                &mut manager,
+                &mut commands,
+                &mut dialogue_state,
+                &mut history,
+                &read_lines,
+                true,
+            );
+        } else {
+            let camera_transform = camera_query.get_single().unwrap();
+            manager.original_camera_transform = Some(*camera_transform);
+        }
+
+        manager.priority = event.priority;
+        manager.enter_scene(event.scene_name.clone(), 0);
+        publish(
+            &mut commands,
+            DialogueStartedEvent {
+                scene: event.scene_name.clone(),
+            },
+        );
+        send_dialogue_events(&mut manager, &mut commands, &mut action_events);
         dialogue_state.active = true;
         next_events.send(NextDialogueEvent);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_next_dialogue(
+    mut commands: Commands,
     mut events: EventReader<NextDialogueEvent>,
     mut dialogue_query: Query<(&mut DialogueManager, &mut DialogueTimer)>,
     mut dialogue_state: ResMut<DialogueState>,
-    mut camera_query: Query<&mut Transform, (With<DialogueCamera>, Without<DialogueTarget>)>,
+    mut stage: DialogueStage,
     mut dialogue_log: ResMut<DialogueLog>,
+    mut read_lines: ResMut<ReadLines>,
+    mut history: ResMut<DialogueHistory>,
+    mut queue: ResMut<DialogueQueue>,
+    mut rng: ResMut<DialogueRng>,
+    locale: Res<Locale>,
+    tables: Res<StringTables>,
+    voice: DialogueVoice,
+    mut start_events: EventWriter<StartDialogueEvent>,
     mut action_events: EventWriter<ActionTriggerEvent>,
-    targets_query: Query<(&Transform, &Speaker), (With<DialogueTarget>, Without<DialogueCamera>)>,
 ) {
     for _ in events.read() {
+        // a second advance in the same batch after the dialogue ended mustn't end it again
+        if !dialogue_state.active {
+            continue;
+        }
+
         let (mut manager, mut timer) = dialogue_query.get_single_mut().unwrap();
 
         if manager.waiting_for_choice {
             return;
         }
 
+        voice.stop(&mut commands);
+
+        // pushed even when the dialogue is about to end, ending clears the history anyway
+        history.push(DialogueSnapshot {
+            scene: manager.current_scene.clone(),
+            line: manager.current_line,
+            ephemeral_line: manager.ephemeral_line.clone(),
+            taken_choices: manager.taken_choices.clone(),
+            variables: manager.variables.clone(),
+            call_stack: manager.call_stack.clone(),
+            locals: manager.locals.clone(),
+            variant_states: manager.variant_states.clone(),
+            scene_visits: manager.scene_visits.clone(),
+            line_visits: manager.line_visits.clone(),
+            rng: rng.clone(),
+            log_len: dialogue_log.total_len(),
+            camera_transform: stage.camera_transform(),
+            actor_transforms: stage.actor_transforms(),
+        });
+
         if let Some(line) = manager.ephemeral_line.take() {
-            dialogue_state.current_text = line.text.clone();
+            let shown = ShownText {
+                id: line.id.clone().unwrap_or_default(),
+                source: line.text.clone(),
+            };
+            let text = manager.render(&shown, &tables, &locale.0);
+            voice.play(&mut commands, line.voice.as_deref(), &shown.id, &locale.0);
+            manager.shown_text = Some(shown);
+            dialogue_state.current_text = text.clone();
+            dialogue_state.shown_lines += 1;
             dialogue_state.current_speaker = line.speaker.clone();
+            dialogue_state.current_title = manager.scene_title(&tables, &locale.0);
 
-            dialogue_log.entries.push(DialogueLogEntry::Line {
-                speaker: line.speaker.clone(),
-                text: line.text.clone(),
-            });
+            dialogue_log.push(
+                &manager.current_scene,
+                None,
+                LogContent::Line {
+                    speaker: line.speaker.clone(),
+                    text: text.clone(),
+                },
+            );
+
+            publish(
+                &mut commands,
+                LineShownEvent {
+                    scene: manager.current_scene.clone(),
+                    line: None,
+                    speaker: line.speaker.clone(),
+                    text,
+                },
+            );
 
             if let Some(camera_target) = &line.camera_target {
-                let mut camera_transform = camera_query.get_single_mut().unwrap();
-                for (target_transform, speaker) in targets_query.iter() {
-                    if speaker.name == *camera_target {
-                        let target_pos = target_transform.translation;
-                        camera_transform.translation = target_pos + Vec3::new(0.0, 2.0, 5.0);
-                        camera_transform.look_at(target_pos + Vec3::new(0.0, 1.0, 0.0), Vec3::Y);
-                        break;
-                    }
-                }
+                stage.focus(camera_target);
             }
 
-            if manager.auto_mode {
-                if let Some(auto_time) = line.auto_time {
-                    timer.0 = Timer::from_seconds(auto_time, TimerMode::Once);
-                }
+            if manager.auto_mode
+                && let Some(auto_time) = line.auto_time
+            {
+                timer.0 = Timer::from_seconds(auto_time, TimerMode::Once);
             }
 
             return;
         }
 
-        if let Some(scene) = manager.dialogue_data.scenes.get(&manager.current_scene) {
-            if manager.current_line < scene.len() {
-                let line = &scene[manager.current_line];
+        // lines whose condition fails are passed over, and silent lines only carry triggers
+        // and directives, so neither of them stops the dialogue
+        let next_line = manager.next_shown_line();
+        send_dialogue_events(&mut manager, &mut commands, &mut action_events);
 
-                dialogue_state.current_text = line.text.clone();
-                dialogue_state.current_speaker = line.speaker.clone();
+        if let Some(line) = next_line {
+            read_lines.mark_read(&manager.current_scene, manager.current_line);
+            manager.visit_current_line();
 
-                dialogue_log.entries.push(DialogueLogEntry::Line {
+            let shown = manager.line_text(&line, &mut rng);
+            let text = manager.render(&shown, &tables, &locale.0);
+            voice.play(&mut commands, line.voice.as_deref(), &shown.id, &locale.0);
+            manager.shown_text = Some(shown);
+            dialogue_state.current_text = text.clone();
+            dialogue_state.shown_lines += 1;
+            dialogue_state.current_speaker = line.speaker.clone();
+            dialogue_state.current_title = manager.scene_title(&tables, &locale.0);
+
+            dialogue_log.push(
+                &manager.current_scene,
+                Some(manager.current_line),
+                LogContent::Line {
                     speaker: line.speaker.clone(),
-                    text: line.text.clone(),
-                });
+                    text: text.clone(),
+                },
+            );
 
-                if let Some(camera_target) = &line.camera_target {
-                    let mut camera_transform = camera_query.get_single_mut().unwrap();
-                    for (target_transform, speaker) in targets_query.iter() {
-                        if speaker.name == *camera_target {
-                            let target_pos = target_transform.translation;
-                            camera_transform.translation = target_pos + Vec3::new(0.0, 2.0, 5.0);
-                            camera_transform
-                                .look_at(target_pos + Vec3::new(0.0, 1.0, 0.0), Vec3::Y);
-                            break;
-                        }
-                    }
-                }
+            publish(
+                &mut commands,
+                LineShownEvent {
+                    scene: manager.current_scene.clone(),
+                    line: Some(manager.current_line),
+                    speaker: line.speaker.clone(),
+                    text,
+                },
+            );
 
-                if let Some(triggers) = &line.triggers {
-                    for trigger in triggers {
-                        action_events.send(ActionTriggerEvent(trigger.clone()));
-                    }
-                }
+            if let Some(camera_target) = &line.camera_target {
+                stage.focus(camera_target);
+            }
 
-                if let Some(choices) = &line.choices {
-                    let choices_clone = choices.clone();
-                    manager.waiting_for_choice = true;
-                    manager.current_choices = choices_clone;
-                } else {
-                    if manager.auto_mode {
-                        if let Some(auto_time) = line.auto_time {
-                            timer.0 = Timer::from_seconds(auto_time, TimerMode::Once);
-                        }
-                    }
+            // before the choices, whose conditions may depend on them
+            manager.fire_triggers(&line.triggers);
+            send_dialogue_events(&mut manager, &mut commands, &mut action_events);
 
-                    manager.current_line += 1;
-                }
+            let presented: Vec<(usize, DialogueChoice)> = line
+                .choices
+                .as_ref()
+                .map(|choices| {
+                    manager
+                        .presentable_choices(choices)
+                        .into_iter()
+                        .map(|i| (i, choices[i].clone()))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if !presented.is_empty() {
+                let default_choice = line
+                    .default_choice
+                    .and_then(|default| presented.iter().position(|(i, _)| *i == default));
+                let choice_timeout = line.choice_timeout.map(|seconds| ChoiceTimeout {
+                    timer: Timer::from_seconds(seconds, TimerMode::Once),
+                    default_choice,
+                    silence: line.silence.clone().map(DialogueChoice::from),
+                });
+                manager.current_choice_indices = presented.into_iter().map(|(i, _)| i).collect();
+                manager.current_choices = manager.localized_choices(&tables, &locale.0);
+                publish(
+                    &mut commands,
+                    ChoicesPresentedEvent {
+                        scene: manager.current_scene.clone(),
+                        line: manager.current_line,
+                        choices: manager.current_choices.clone(),
+                    },
+                );
+                manager.waiting_for_choice = true;
+                manager.focused_choice = 0;
+                manager.choice_timeout = choice_timeout;
             } else {
-                if let Some(original_transform) = manager.original_camera_transform {
-                    let mut camera_transform = camera_query.get_single_mut().unwrap();
-                    *camera_transform = original_transform;
+                if manager.auto_mode
+                    && let Some(auto_time) = line.auto_time
+                {
+                    timer.0 = Timer::from_seconds(auto_time, TimerMode::Once);
                 }
 
-                dialogue_state.active = false;
-                dialogue_state.current_text.clear();
-                dialogue_state.current_speaker.clear();
+                manager.finish_line(&line);
+                send_dialogue_events(&mut manager, &mut commands, &mut action_events);
+            }
+        } else {
+            if let Some(original_transform) = manager.original_camera_transform {
+                stage.set_camera_transform(original_transform);
+            }
+
+            end_dialogue(
+                &mut manager,
+                &mut commands,
+                &mut dialogue_state,
+                &mut history,
+                &read_lines,
+                false,
+            );
+
+            if let Some(next) = queue.pending.pop_front() {
+                start_events.send(next);
+            }
+        }
+    }
+}
+
+/// closes the dialogue without touching the camera, which an interrupting dialogue keeps using
+fn end_dialogue(
+    manager: &mut DialogueManager,
+    commands: &mut Commands,
+    dialogue_state: &mut DialogueState,
+    history: &mut DialogueHistory,
+    read_lines: &ReadLines,
+    cancelled: bool,
+) {
+    manager.reset_conversation();
+    history.snapshots.clear();
+    dialogue_state.active = false;
+    dialogue_state.current_text.clear();
+    dialogue_state.current_speaker.clear();
+    dialogue_state.current_title = None;
+
+    publish(
+        commands,
+        DialogueEndedEvent {
+            scene: manager.current_scene.clone(),
+            cancelled,
+        },
+    );
+
+    save_progress(manager, read_lines);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_cancel_dialogue(
+    mut commands: Commands,
+    voice: DialogueVoice,
+    mut events: EventReader<CancelDialogueEvent>,
+    mut dialogue_query: Query<&mut DialogueManager>,
+    mut dialogue_state: ResMut<DialogueState>,
+    mut history: ResMut<DialogueHistory>,
+    mut queue: ResMut<DialogueQueue>,
+    read_lines: Res<ReadLines>,
+    mut start_events: EventWriter<StartDialogueEvent>,
+    mut action_events: EventWriter<ActionTriggerEvent>,
+    mut camera_query: Query<&mut Transform, With<DialogueCamera>>,
+) {
+    for event in events.read() {
+        if event.clear_queue {
+            queue.pending.clear();
+        }
+
+        if !dialogue_state.active {
+            continue;
+        }
+
+        let mut manager = dialogue_query.get_single_mut().unwrap();
+        manager.leave_dialogue();
+        send_dialogue_events(&mut manager, &mut commands, &mut action_events);
+        voice.stop(&mut commands);
+
+        if let Some(original_transform) = manager.original_camera_transform
+            && let Ok(mut camera_transform) = camera_query.get_single_mut()
+        {
+            *camera_transform = original_transform;
+        }
+
+        end_dialogue(
+            &mut manager,
+            &mut commands,
+            &mut dialogue_state,
+            &mut history,
+            &read_lines,
+            true,
+        );
+
+        if let Some(next) = queue.pending.pop_front() {
+            start_events.send(next);
+        }
+    }
+}
+
+/// sends an outgoing event both to event readers and to observers
+fn publish<E: Event + Clone>(commands: &mut Commands, event: E) {
+    commands.queue(move |world: &mut World| {
+        world.send_event(event.clone());
+        world.trigger(event);
+    });
+}
+
+/// sends out the triggers the manager fired since the last call and announces the scene
+/// now running
+fn send_dialogue_events(
+    manager: &mut DialogueManager,
+    commands: &mut Commands,
+    action_events: &mut EventWriter<ActionTriggerEvent>,
+) {
+    for trigger in std::mem::take(&mut manager.fired_triggers) {
+        action_events.send(ActionTriggerEvent(trigger));
+    }
+
+    for transition in std::mem::take(&mut manager.scene_transitions) {
+        match transition {
+            SceneTransition::Entered(scene) | SceneTransition::Resumed(scene) => {
+                publish(commands, SceneChangedEvent { scene });
             }
+            SceneTransition::Left(_) => {}
         }
     }
 }
 
 pub fn handle_auto_dialogue(
     time: Res<Time>,
-    mut dialogue_query: Query<(&DialogueManager, &mut DialogueTimer)>,
+    mut dialogue_query: Query<(&mut DialogueManager, &mut DialogueTimer)>,
     dialogue_state: Res<DialogueState>,
+    read_lines: Res<ReadLines>,
+    voice: DialogueVoice,
     mut next_events: EventWriter<NextDialogueEvent>,
 ) {
     if !dialogue_state.active {
         return;
     }
 
-    if let Ok((manager, mut timer)) = dialogue_query.get_single_mut() {
+    if let Ok((mut manager, mut timer)) = dialogue_query.get_single_mut() {
+        if manager.skip_mode || manager.skip_held {
+            // skipping stops at choices and at anything the player hasn't seen yet
+            if manager.waiting_for_choice || manager.next_line_unread(&read_lines) {
+                manager.skip_mode = false;
+            } else {
+                next_events.send(NextDialogueEvent);
+                return;
+            }
+        }
+
         if manager.auto_mode && !manager.waiting_for_choice {
+            // a line with voice-over stays up until the clip is done, however short its timer
             timer.0.tick(time.delta());
-            if timer.0.finished() {
+            if timer.0.finished() && !voice.is_playing() {
                 next_events.send(NextDialogueEvent);
             }
         }
     }
 }
 
+pub fn save_progress_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    dialogue_query: Query<&DialogueManager>,
+    read_lines: Res<ReadLines>,
+    dialogue_log: Res<DialogueLog>,
+) {
+    if exit_events.read().next().is_some()
+        && let Ok(manager) = dialogue_query.get_single()
+    {
+        save_progress(manager, &read_lines);
+
+        if let Err(err) = dialogue_log.save(TRANSCRIPT_PATH, LogFormat::Json) {
+            error!("failed to save the dialogue transcript: {err}");
+        }
+    }
+}
+
+/// writes the log to a file named after the time of the export, for attaching to bug reports
+pub fn handle_export_log(mut events: EventReader<ExportLogEvent>, dialogue_log: Res<DialogueLog>) {
+    for event in events.read() {
+        let timestamp = format_timestamp(std::time::SystemTime::now()).replace(':', "-");
+        let path = format!(
+            "{TRANSCRIPTS_DIR}/transcript-{timestamp}.{}",
+            event.0.extension()
+        );
+
+        match dialogue_log.save(&path, event.0) {
+            Ok(()) => info!("exported the dialogue log to {path}"),
+            Err(err) => error!("failed to export the dialogue log to {path}: {err}"),
+        }
+    }
+}
+
+fn save_progress(manager: &DialogueManager, read_lines: &ReadLines) {
+    if let Err(err) = read_lines.save(READ_LINES_PATH) {
+        error!("failed to save read lines: {err}");
+    }
+
+    if let Err(err) = DialogueSave::from_manager(manager).save(SAVE_PATH) {
+        error!("failed to save dialogue progress: {err}");
+    }
+}
+
 pub fn handle_toggle_auto(
     mut events: EventReader<ToggleAutoEvent>,
     mut dialogue_query: Query<&mut DialogueManager>,
@@ -189,6 +515,32 @@ pub fn handle_toggle_auto(
     }
 }
 
+pub fn handle_toggle_skip(
+    mut events: EventReader<ToggleSkipEvent>,
+    mut dialogue_query: Query<&mut DialogueManager>,
+) {
+    for _ in events.read() {
+        if let Ok(mut manager) = dialogue_query.get_single_mut() {
+            manager.skip_mode = !manager.skip_mode;
+        }
+    }
+}
+
+pub fn handle_rebind_action(
+    mut events: EventReader<RebindActionEvent>,
+    mut input_map: ResMut<InputMap>,
+) {
+    let mut changed = false;
+    for event in events.read() {
+        input_map.rebind(event.action, event.inputs.clone());
+        changed = true;
+    }
+
+    if changed && let Err(err) = input_map.save(INPUT_SETTINGS_PATH) {
+        error!("failed to save input settings: {err}");
+    }
+}
+
 pub fn handle_reset_scene(
     mut query: Query<&mut Transform, With<Actor>>,
     mut events: EventReader<ResetSceneEvent>,
@@ -199,36 +551,108 @@ pub fn handle_reset_scene(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_input(
-    keys: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    input: DialogueInput,
     mut next_events: EventWriter<NextDialogueEvent>,
     mut auto_events: EventWriter<ToggleAutoEvent>,
+    mut skip_events: EventWriter<ToggleSkipEvent>,
     mut start_events: EventWriter<StartDialogueEvent>,
+    mut choice_events: EventWriter<MakeChoiceEvent>,
+    mut rollback_events: EventWriter<RollbackEvent>,
+    mut cancel_events: EventWriter<CancelDialogueEvent>,
     mut dialogue_log: ResMut<DialogueLog>,
     dialogue_state: Res<DialogueState>,
+    mut typewriter: ResMut<Typewriter>,
+    mut dialogue_query: Query<&mut DialogueManager>,
 ) {
-    if keys.just_pressed(KeyCode::Space) && dialogue_state.active {
-        next_events.send(NextDialogueEvent);
+    let mut manager = dialogue_query.get_single_mut().unwrap();
+
+    // typing into the log's search box shouldn't also advance or toggle things
+    if contexts
+        .try_ctx_mut()
+        .is_some_and(|ctx| ctx.wants_keyboard_input())
+    {
+        manager.skip_held = false;
+        return;
     }
 
-    if keys.just_pressed(KeyCode::KeyA) {
+    // confirm and advance can share a button, so only advance outside of choices
+    if input.just_pressed(DialogueAction::Advance)
+        && dialogue_state.active
+        && !manager.waiting_for_choice
+    {
+        if typewriter.is_revealing() {
+            typewriter.finish();
+        } else {
+            next_events.send(NextDialogueEvent);
+        }
+    }
+
+    if input.just_pressed(DialogueAction::ToggleAuto) {
         auto_events.send(ToggleAutoEvent);
     }
 
-    if keys.just_pressed(KeyCode::KeyT) {
-        if !dialogue_state.active {
-            start_events.send(StartDialogueEvent {
-                scene_name: "intro".to_string(),
-            });
+    if input.just_pressed(DialogueAction::Skip) && dialogue_state.active {
+        skip_events.send(ToggleSkipEvent);
+    }
+
+    manager.skip_held = dialogue_state.active && input.pressed(DialogueAction::HoldSkip);
+
+    if dialogue_state.active {
+        if input.just_pressed(DialogueAction::Rollback) {
+            rollback_events.send(RollbackEvent { to_choice: false });
+        } else if input.just_pressed(DialogueAction::RollbackToChoice) {
+            rollback_events.send(RollbackEvent { to_choice: true });
         }
     }
 
-    if keys.just_pressed(KeyCode::KeyL) {
+    if input.just_pressed(DialogueAction::Cancel) && dialogue_state.active {
+        cancel_events.send(CancelDialogueEvent { clear_queue: false });
+    }
+
+    if input.just_pressed(DialogueAction::StartDialogue) && !dialogue_state.active {
+        start_events.send(StartDialogueEvent {
+            scene_name: "intro".to_string(),
+            priority: DialoguePriority::Normal,
+        });
+    }
+
+    if input.just_pressed(DialogueAction::ToggleLog) {
         dialogue_log.show_log = !dialogue_log.show_log;
     }
+
+    if dialogue_state.active && manager.waiting_for_choice && !manager.current_choices.is_empty() {
+        let choice_count = manager.current_choices.len();
+
+        if input.just_pressed(DialogueAction::ChoiceUp) {
+            manager.focused_choice = (manager.focused_choice + choice_count - 1) % choice_count;
+        }
+
+        if input.just_pressed(DialogueAction::ChoiceDown) {
+            manager.focused_choice = (manager.focused_choice + 1) % choice_count;
+        }
+
+        if input.just_pressed(DialogueAction::ChoiceConfirm) {
+            choice_events.send(MakeChoiceEvent {
+                choice_index: manager.focused_choice,
+            });
+            return;
+        }
+
+        for choice_index in 0..choice_count {
+            if input.just_pressed(DialogueAction::SelectChoice(choice_index)) {
+                manager.focused_choice = choice_index;
+                choice_events.send(MakeChoiceEvent { choice_index });
+                break;
+            }
+        }
+    }
 }
 
 pub fn handle_choice(
+    mut commands: Commands,
     mut events: EventReader<MakeChoiceEvent>,
     mut dialogue_query: Query<&mut DialogueManager>,
     mut next_events: EventWriter<NextDialogueEvent>,
@@ -238,48 +662,177 @@ pub fn handle_choice(
     for event in events.read() {
         let mut manager = dialogue_query.get_single_mut().unwrap();
         if manager.waiting_for_choice && event.choice_index < manager.current_choices.len() {
-            dialogue_log.entries.push(DialogueLogEntry::Choices {
-                options: manager.current_choices.clone(),
-                selected_index: event.choice_index,
-            });
+            dialogue_log.push(
+                &manager.current_scene,
+                Some(manager.current_line),
+                LogContent::Choices {
+                    options: manager.current_choices.clone(),
+                    selected_index: Some(event.choice_index),
+                },
+            );
+
+            let choice_index = manager.current_choice_indices[event.choice_index];
+            let choice_key = manager.choice_key(choice_index);
+            manager.taken_choices.insert(choice_key);
 
             let choice = manager.current_choices[event.choice_index].clone();
+            apply_choice(
+                &mut manager,
+                &mut commands,
+                &choice,
+                Some(choice_index),
+                &mut action_events,
+            );
+
+            next_events.send(NextDialogueEvent);
+        }
+    }
+}
 
-            if let Some(triggers) = &choice.triggers {
-                for trigger in triggers {
-                    action_events.send(ActionTriggerEvent(trigger.clone()));
+pub fn handle_choice_timeout(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dialogue_query: Query<&mut DialogueManager>,
+    mut next_events: EventWriter<NextDialogueEvent>,
+    mut choice_events: EventWriter<MakeChoiceEvent>,
+    mut dialogue_log: ResMut<DialogueLog>,
+    mut action_events: EventWriter<ActionTriggerEvent>,
+) {
+    let Ok(mut manager) = dialogue_query.get_single_mut() else {
+        return;
+    };
+
+    if !manager.waiting_for_choice {
+        return;
+    }
+
+    let Some(choice_timeout) = manager.choice_timeout.as_mut() else {
+        return;
+    };
+
+    choice_timeout.timer.tick(time.delta());
+    if !choice_timeout.timer.finished() {
+        return;
+    }
+
+    let choice_timeout = manager.choice_timeout.take().unwrap();
+
+    if let Some(choice_index) = choice_timeout.default_choice {
+        choice_events.send(MakeChoiceEvent { choice_index });
+        return;
+    }
+
+    // without an explicit silence outcome, not answering just moves on to the next line
+    let silence = choice_timeout
+        .silence
+        .unwrap_or_else(|| SilenceOutcome::default().into());
+
+    dialogue_log.push(
+        &manager.current_scene,
+        Some(manager.current_line),
+        LogContent::Choices {
+            options: manager.current_choices.clone(),
+            selected_index: None,
+        },
+    );
+
+    apply_choice(
+        &mut manager,
+        &mut commands,
+        &silence,
+        None,
+        &mut action_events,
+    );
+
+    next_events.send(NextDialogueEvent);
+}
+
+/// fires the choice triggers, queues the echoed player line and jumps to the choice target
+fn apply_choice(
+    manager: &mut DialogueManager,
+    commands: &mut Commands,
+    choice: &DialogueChoice,
+    choice_index: Option<usize>,
+    action_events: &mut EventWriter<ActionTriggerEvent>,
+) {
+    publish(
+        commands,
+        ChoiceMadeEvent {
+            scene: manager.current_scene.clone(),
+            line: manager.current_line,
+            choice: choice_index,
+        },
+    );
+
+    manager.choose(choice, choice_index);
+    send_dialogue_events(manager, commands, action_events);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_rollback(
+    mut commands: Commands,
+    mut events: EventReader<RollbackEvent>,
+    mut dialogue_query: Query<&mut DialogueManager>,
+    mut history: ResMut<DialogueHistory>,
+    mut dialogue_log: ResMut<DialogueLog>,
+    mut rng: ResMut<DialogueRng>,
+    mut next_events: EventWriter<NextDialogueEvent>,
+    mut camera_query: Query<&mut Transform, (With<DialogueCamera>, Without<Actor>)>,
+    mut actors_query: Query<&mut Transform, (With<Actor>, Without<DialogueCamera>)>,
+    dialogue_state: Res<DialogueState>,
+) {
+    for event in events.read() {
+        if !dialogue_state.active || history.snapshots.len() < 2 {
+            continue;
+        }
+
+        let mut manager = dialogue_query.get_single_mut().unwrap();
+
+        // the newest snapshot is the line on screen, so the one before it is the line to go back to
+        let current = history.snapshots.pop_back().unwrap();
+        let target_index = if event.to_choice {
+            // snapshots are taken before the silent and skipped lines in front of a line are
+            // passed over, so look ahead from each one for the line it went on to show
+            history.snapshots.iter().rposition(|snapshot| {
+                snapshot.ephemeral_line.is_none() && {
+                    let mut peek = manager.clone();
+                    snapshot.restore(&mut peek);
+                    peek.peek_shown_line()
+                        .is_some_and(|(_, line)| line.choices.is_some())
                 }
-            }
+            })
+        } else {
+            history.snapshots.len().checked_sub(1)
+        };
 
-            let player_line = DialogueLine {
-                speaker: PLAYER_NAME.to_string(),
-                text: choice
-                    .player_text
-                    .as_ref()
-                    .cloned()
-                    .unwrap_or_else(|| choice.text.clone()),
-                auto_time: choice.player_text_auto_time.clone(),
-                camera_target: Some(PLAYER_NAME.to_string()),
-                choices: None,
-                triggers: None,
-            };
+        let Some(target_index) = target_index else {
+            history.snapshots.push_back(current);
+            continue;
+        };
 
-            manager.ephemeral_line = Some(player_line);
+        history.snapshots.truncate(target_index + 1);
+        let snapshot = history.snapshots.pop_back().unwrap();
 
-            if let Some(next_scene) = &choice.next_scene {
-                manager.current_scene = next_scene.clone();
-                manager.current_line = 0;
-            } else if let Some(next_line) = choice.next_line {
-                manager.current_line = next_line;
-            } else {
-                manager.current_line += 1;
-            }
+        snapshot.restore(&mut manager);
+        *rng = snapshot.rng;
 
-            manager.waiting_for_choice = false;
-            manager.current_choices.clear();
+        dialogue_log.truncate(snapshot.log_len);
 
-            next_events.send(NextDialogueEvent);
+        if let Some(camera_transform) = snapshot.camera_transform
+            && let Ok(mut transform) = camera_query.get_single_mut()
+        {
+            *transform = camera_transform;
+        }
+
+        for (entity, actor_transform) in snapshot.actor_transforms {
+            if let Ok(mut transform) = actors_query.get_mut(entity) {
+                *transform = actor_transform;
+                commands.entity(entity).remove::<MovementGoal>();
+            }
         }
+
+        // shows the restored line again, which records a fresh snapshot for it
+        next_events.send(NextDialogueEvent);
     }
 }
 
@@ -321,6 +874,60 @@ pub fn handle_move_to_trigger(
     }
 }
 
+pub fn handle_set_variable_trigger(
+    mut events: EventReader<ActionTriggerEvent>,
+    mut dialogue_query: Query<&mut DialogueManager>,
+) {
+    for event in events.read() {
+        if event.0.trigger_type != "set_variable" {
+            continue;
+        }
+
+        let Some(value) = event.0.params.get("value") else {
+            warn!(
+                "set_variable trigger for {} is missing a value",
+                event.0.target
+            );
+            continue;
+        };
+
+        if let Ok(mut manager) = dialogue_query.get_single_mut() {
+            manager
+                .variables
+                .insert(event.0.target.clone(), value.clone());
+        }
+    }
+}
+
+/// starts the target scene, with an optional `priority` param deciding whether it
+/// interrupts or queues behind the running dialogue
+pub fn handle_start_dialogue_trigger(
+    mut events: EventReader<ActionTriggerEvent>,
+    mut start_events: EventWriter<StartDialogueEvent>,
+) {
+    for event in events.read() {
+        if event.0.trigger_type != "start_dialogue" {
+            continue;
+        }
+
+        let priority = match event.0.params.get("priority") {
+            Some(priority) => match serde_json::from_value(priority.clone()) {
+                Ok(priority) => priority,
+                Err(err) => {
+                    warn!("invalid priority for {}: {err}", event.0.target);
+                    continue;
+                }
+            },
+            None => DialoguePriority::default(),
+        };
+
+        start_events.send(StartDialogueEvent {
+            scene_name: event.0.target.clone(),
+            priority,
+        });
+    }
+}
+
 pub fn process_movement(
     mut commands: Commands,
     time: Res<Time>,
@@ -340,18 +947,147 @@ pub fn process_movement(
     }
 }
 
+/// reveals the current line a few characters at a time, blipping for speakers without
+/// voice-over
+#[allow(clippy::too_many_arguments)]
+pub fn update_typewriter(
+    mut commands: Commands,
+    time: Res<Time>,
+    dialogue_state: Res<DialogueState>,
+    mut typewriter: ResMut<Typewriter>,
+    dialogue_query: Query<&DialogueManager>,
+    speakers_query: Query<(&Speaker, &BlipProfile)>,
+    voice: DialogueVoice,
+    mut blips: ResMut<Assets<Blip>>,
+) {
+    if typewriter.line != dialogue_state.shown_lines {
+        typewriter.start(dialogue_state.shown_lines, &dialogue_state.current_text);
+    } else if typewriter.text != dialogue_state.current_text {
+        typewriter.retext(&dialogue_state.current_text);
+    }
+
+    let manager = dialogue_query.get_single().unwrap();
+    if manager.skip_mode || manager.skip_held {
+        typewriter.finish();
+    }
+
+    if !typewriter.is_revealing() {
+        return;
+    }
+
+    typewriter.timer.tick(time.delta());
+    let count = typewriter.timer.times_finished_this_tick() as usize;
+    let revealed = typewriter.reveal(count);
+
+    // one blip per frame at most, and none for spaces and punctuation
+    if voice.is_playing() || !revealed.chars().any(char::is_alphanumeric) {
+        return;
+    }
+
+    let Some((_, profile)) = speakers_query
+        .iter()
+        .find(|(speaker, _)| speaker.name == dialogue_state.current_speaker)
+    else {
+        return;
+    };
+
+    let variance = profile.variance * rand::thread_rng().gen_range(-1.0..=1.0);
+    commands.spawn((
+        AudioPlayer(blips.add(Blip {
+            frequency: profile.pitch * (1.0 + variance),
+            waveform: profile.waveform,
+        })),
+        PlaybackSettings::DESPAWN,
+    ));
+}
+
+/// drops voice clips that failed to load, so auto mode doesn't wait for them forever
+pub fn cleanup_failed_voice(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    clips: Query<(Entity, &AudioPlayer), With<VoiceClip>>,
+) {
+    for (entity, player) in &clips {
+        if matches!(
+            asset_server.load_state(&player.0),
+            bevy::asset::LoadState::Failed(_)
+        ) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// re-renders the line and choices on screen when the locale changes
+pub fn handle_locale_change(
+    locale: Res<Locale>,
+    tables: Res<StringTables>,
+    mut dialogue_state: ResMut<DialogueState>,
+    mut dialogue_query: Query<&mut DialogueManager>,
+) {
+    if !locale.is_changed() || locale.is_added() || !dialogue_state.active {
+        return;
+    }
+
+    let Ok(mut manager) = dialogue_query.get_single_mut() else {
+        return;
+    };
+
+    if let Some(shown) = &manager.shown_text {
+        let text = manager.render(shown, &tables, &locale.0);
+        dialogue_state.current_text = text;
+    }
+    dialogue_state.current_title = manager.scene_title(&tables, &locale.0);
+
+    if manager.waiting_for_choice {
+        manager.current_choices = manager.localized_choices(&tables, &locale.0);
+    }
+}
+
+pub fn update_dialogue_mode(
+    dialogue_state: Res<DialogueState>,
+    dialogue_query: Query<&DialogueManager>,
+    moving_actors: Query<(), (With<Actor>, With<MovementGoal>)>,
+    mode: Res<State<DialogueMode>>,
+    mut next_mode: ResMut<NextState<DialogueMode>>,
+) {
+    let Ok(manager) = dialogue_query.get_single() else {
+        return;
+    };
+
+    let new_mode = if !dialogue_state.active {
+        DialogueMode::Idle
+    } else if manager.waiting_for_choice {
+        DialogueMode::Choosing
+    } else if !moving_actors.is_empty() {
+        DialogueMode::WaitingForAction
+    } else {
+        DialogueMode::Talking
+    };
+
+    if *mode.get() != new_mode {
+        next_mode.set(new_mode);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn dialogue_ui(
     mut contexts: EguiContexts,
     dialogue_state: Res<DialogueState>,
-    dialogue_query: Query<&DialogueManager>,
+    mut dialogue_query: Query<&mut DialogueManager>,
     mut next_events: EventWriter<NextDialogueEvent>,
     mut auto_events: EventWriter<ToggleAutoEvent>,
+    mut skip_events: EventWriter<ToggleSkipEvent>,
     mut start_events: EventWriter<StartDialogueEvent>,
     mut dialogue_log: ResMut<DialogueLog>,
     mut choice_events: EventWriter<MakeChoiceEvent>,
     mut reset_events: EventWriter<ResetSceneEvent>,
+    mut rollback_events: EventWriter<RollbackEvent>,
+    history: Res<DialogueHistory>,
+    mut locale: ResMut<Locale>,
+    tables: Res<StringTables>,
+    mut typewriter: ResMut<Typewriter>,
 ) {
-    let manager = dialogue_query.get_single().unwrap();
+    let mut manager = dialogue_query.get_single_mut().unwrap();
 
     egui::Window::new("Dialogue")
         .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -50.0))
@@ -363,6 +1099,14 @@ pub fn dialogue_ui(
 
             if dialogue_state.active {
                 ui.vertical(|ui| {
+                    if let Some(title) = &dialogue_state.current_title {
+                        ui.label(
+                            egui::RichText::new(title)
+                                .small()
+                                .color(egui::Color32::GRAY),
+                        );
+                    }
+
                     ui.label(
                         egui::RichText::new(&dialogue_state.current_speaker)
                             .heading()
@@ -372,7 +1116,7 @@ pub fn dialogue_ui(
                     ui.separator();
 
                     ui.label(
-                        egui::RichText::new(&dialogue_state.current_text)
+                        egui::RichText::new(typewriter.visible())
                             .size(16.0)
                             .color(egui::Color32::WHITE),
                     );
@@ -380,8 +1124,19 @@ pub fn dialogue_ui(
                     ui.separator();
 
                     ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(history.snapshots.len() > 1, egui::Button::new("Back"))
+                            .clicked()
+                        {
+                            rollback_events.send(RollbackEvent { to_choice: false });
+                        }
+
                         if ui.button("Next").clicked() {
-                            next_events.send(NextDialogueEvent);
+                            if typewriter.is_revealing() {
+                                typewriter.finish();
+                            } else {
+                                next_events.send(NextDialogueEvent);
+                            }
                         }
 
                         let auto_text = if manager.auto_mode {
@@ -393,9 +1148,20 @@ pub fn dialogue_ui(
                             auto_events.send(ToggleAutoEvent);
                         }
 
+                        let skip_text = if manager.skip_mode {
+                            "Skip: ON"
+                        } else {
+                            "Skip: OFF"
+                        };
+                        if ui.button(skip_text).clicked() {
+                            skip_events.send(ToggleSkipEvent);
+                        }
+
                         if ui.button("Log").clicked() {
                             dialogue_log.show_log = !dialogue_log.show_log;
                         }
+
+                        locale_picker(ui, &mut locale, &tables);
                     });
                 });
             } else {
@@ -404,11 +1170,13 @@ pub fn dialogue_ui(
                     if ui.button("Start Dialogue").clicked() {
                         start_events.send(StartDialogueEvent {
                             scene_name: "intro".to_string(),
+                            priority: DialoguePriority::Normal,
                         });
                     }
                     if ui.button("Reset Scene").clicked() {
                         reset_events.send(ResetSceneEvent);
                     }
+                    locale_picker(ui, &mut locale, &tables);
                 });
             }
         });
@@ -423,21 +1191,97 @@ pub fn dialogue_ui(
                 ui.set_min_width(150.0);
 
                 ui.vertical(|ui| {
+                    // a resting cursor would otherwise undo every keyboard and gamepad move
+                    let pointer_moved = ui.input(|input| input.pointer.is_moving());
+                    let mut hovered_choice = None;
+
                     for (i, choice) in manager.current_choices.iter().enumerate() {
-                        if ui.button(&choice.text).clicked() {
+                        let focused = i == manager.focused_choice;
+                        let dimmed = !choice.sticky && manager.is_choice_taken(i);
+                        let mut label = if focused {
+                            egui::RichText::new(format!("> {}. {}", i + 1, choice.text))
+                                .strong()
+                                .color(egui::Color32::YELLOW)
+                        } else {
+                            egui::RichText::new(format!("  {}. {}", i + 1, choice.text))
+                        };
+                        if dimmed {
+                            label = label.italics();
+                            if !focused {
+                                label = label.color(egui::Color32::DARK_GRAY);
+                            }
+                        }
+
+                        let response = ui.selectable_label(focused, label);
+                        if response.hovered() && pointer_moved {
+                            hovered_choice = Some(i);
+                        }
+                        if response.clicked() {
                             choice_events.send(MakeChoiceEvent { choice_index: i });
                         }
                     }
+
+                    if let Some(focused) = manager.current_choices.get(manager.focused_choice)
+                        && let Some(player_text) = &focused.player_text
+                    {
+                        ui.separator();
+                        ui.label(
+                            egui::RichText::new(player_text)
+                                .italics()
+                                .color(egui::Color32::GRAY),
+                        );
+                    }
+
+                    if let Some(choice_timeout) = &manager.choice_timeout {
+                        let remaining = choice_timeout.timer.remaining_secs();
+                        ui.separator();
+                        ui.add(
+                            egui::ProgressBar::new(choice_timeout.timer.fraction_remaining())
+                                .text(format!("{remaining:.1}s")),
+                        );
+                    }
+
+                    if let Some(i) = hovered_choice {
+                        manager.focused_choice = i;
+                    }
                 });
             });
     }
 }
 
-pub fn dialogue_log_ui(mut contexts: EguiContexts, dialogue_log: Res<DialogueLog>) {
+fn locale_picker(ui: &mut egui::Ui, locale: &mut ResMut<Locale>, tables: &StringTables) {
+    // edits a copy so the locale is only marked as changed when another one is picked
+    let mut selected = locale.0.clone();
+    egui::ComboBox::from_id_salt("locale")
+        .selected_text(&selected)
+        .show_ui(ui, |ui| {
+            for available in tables.locales() {
+                ui.selectable_value(&mut selected, available.clone(), available);
+            }
+        });
+
+    if selected != locale.0 {
+        locale.0 = selected;
+    }
+}
+
+pub fn dialogue_log_ui(
+    mut contexts: EguiContexts,
+    dialogue_log: Res<DialogueLog>,
+    mut log_view: ResMut<LogView>,
+    mut export_events: EventWriter<ExportLogEvent>,
+) {
     if !dialogue_log.show_log {
         return;
     }
 
+    let speakers: BTreeSet<&str> = dialogue_log.entries.iter().map(|e| e.speaker()).collect();
+    let scenes: BTreeSet<&str> = dialogue_log
+        .entries
+        .iter()
+        .map(|e| e.scene.as_str())
+        .collect();
+
     egui::Window::new("Log")
         .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-20.0, 20.0))
         .resizable(true)
@@ -445,47 +1289,144 @@ pub fn dialogue_log_ui(mut contexts: EguiContexts, dialogue_log: Res<DialogueLog
         .default_width(400.0)
         .max_height(500.0)
         .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Export:");
+                for (label, format) in [
+                    ("Markdown", LogFormat::Markdown),
+                    ("Text", LogFormat::Text),
+                    ("JSON", LogFormat::Json),
+                ] {
+                    if ui.button(label).clicked() {
+                        export_events.send(ExportLogEvent(format));
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut log_view.search)
+                        .hint_text("Search")
+                        .desired_width(140.0),
+                );
+                log_filter_picker(
+                    ui,
+                    "log_speaker",
+                    "All speakers",
+                    &mut log_view.speaker,
+                    &speakers,
+                );
+                log_filter_picker(ui, "log_scene", "All scenes", &mut log_view.scene, &scenes);
+            });
+
+            ui.separator();
+
             egui::ScrollArea::vertical()
                 .stick_to_bottom(true)
                 .show(ui, |ui| {
-                    for entry in &dialogue_log.entries {
-                        match entry {
-                            DialogueLogEntry::Line { speaker, text } => {
-                                ui.horizontal(|ui| {
-                                    ui.label(
-                                        egui::RichText::new(speaker)
-                                            .strong()
-                                            .color(egui::Color32::YELLOW),
-                                    );
-                                    ui.label(text);
-                                });
-                            }
-                            DialogueLogEntry::Choices {
-                                options,
-                                selected_index,
-                            } => {
-                                ui.vertical(|ui| {
-                                    for (i, choice) in options.iter().enumerate() {
-                                        let choice_text =
-                                            choice.player_text.as_ref().unwrap_or(&choice.text);
-
-                                        let text_color = if i == *selected_index {
-                                            egui::Color32::WHITE
-                                        } else {
-                                            egui::Color32::GRAY
-                                        };
-
-                                        ui.label(
-                                            egui::RichText::new(choice_text)
-                                                .color(text_color)
-                                                .italics(),
-                                        );
+                    // selectable text would swallow the clicks that select entries
+                    ui.style_mut().interaction.selectable_labels = false;
+
+                    let entries = &dialogue_log.entries;
+                    let mut start = 0;
+                    while start < entries.len() {
+                        // consecutive entries from the same scene share a heading
+                        let scene = &entries[start].scene;
+                        let end = (start..entries.len())
+                            .find(|&i| entries[i].scene != *scene)
+                            .unwrap_or(entries.len());
+                        let shown: Vec<usize> = (start..end)
+                            .filter(|&i| log_view.shows(&entries[i]))
+                            .collect();
+
+                        if !shown.is_empty() {
+                            egui::CollapsingHeader::new(format!("{scene} ({})", shown.len()))
+                                .id_salt(("log_scene", dialogue_log.dropped + start))
+                                .default_open(true)
+                                .show(ui, |ui| {
+                                    for i in shown {
+                                        let position = dialogue_log.dropped + i;
+                                        let selected = log_view.selected == Some(position);
+                                        if log_entry_ui(ui, &entries[i], selected).clicked() {
+                                            log_view.selected = (!selected).then_some(position);
+                                        }
                                     }
                                 });
-                            }
                         }
-                        ui.separator();
+
+                        start = end;
                     }
                 });
         });
 }
+
+/// one log entry, with its location and time underneath when it's selected
+fn log_entry_ui(ui: &mut egui::Ui, entry: &DialogueLogEntry, selected: bool) -> egui::Response {
+    let response = ui
+        .vertical(|ui| {
+            match &entry.content {
+                LogContent::Line { speaker, text } => {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new(speaker)
+                                .strong()
+                                .color(egui::Color32::YELLOW),
+                        );
+                        ui.label(text);
+                    });
+                }
+                LogContent::Choices {
+                    options,
+                    selected_index,
+                } => {
+                    for (i, choice) in options.iter().enumerate() {
+                        let text_color = if Some(i) == *selected_index {
+                            egui::Color32::WHITE
+                        } else {
+                            egui::Color32::GRAY
+                        };
+
+                        ui.label(
+                            egui::RichText::new(choice_label(choice))
+                                .color(text_color)
+                                .italics(),
+                        );
+                    }
+                }
+            }
+
+            if selected {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "{} at {}",
+                        entry.location(),
+                        format_timestamp(entry.time)
+                    ))
+                    .small()
+                    .color(egui::Color32::LIGHT_BLUE),
+                );
+            }
+        })
+        .response
+        .interact(egui::Sense::click())
+        .on_hover_cursor(egui::CursorIcon::PointingHand);
+
+    ui.separator();
+    response
+}
+
+fn log_filter_picker(
+    ui: &mut egui::Ui,
+    id: &str,
+    all: &str,
+    filter: &mut Option<String>,
+    values: &BTreeSet<&str>,
+) {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(filter.as_deref().unwrap_or(all))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(filter, None, all);
+            for value in values {
+                ui.selectable_value(filter, Some(value.to_string()), *value);
+            }
+        });
+}
@@ -0,0 +1,1305 @@
+//! command line tools for the dialogue data, run with `cargo run -- <command>` instead of
+//! starting the game
+
+use std::{collections::BTreeMap, fmt::Write, fs, path::Path};
+
+use bevy::utils::{HashMap, HashSet};
+use serde_json::{Map, Value};
+
+use crate::PLAYER_NAME;
+use crate::dialogue::{
+    DialogueChoice, DialogueData, DialogueScene,
+    components::{DialogueManager, ShownText},
+    resources::{DialogueRng, LOCALES_DIR, SOURCE_LOCALE, StringTables, Translation},
+    voice::recording_path,
+};
+
+pub const DIALOGUE_PATH: &str = "assets/dialogue.json";
+
+const USAGE: &str = "usage:
+  export-po <locale> <file> [--all]     write untranslated and changed strings to a .po file
+  export-xliff <locale> <file> [--all]  same as export-po, as XLIFF 1.2
+  import <locale> <file>                read a translated .po, .xlf or .xliff file into
+                                        assets/locales/<locale>.json
+  assign-ids [file]                     write an explicit id into every line and choice
+                                        without one, defaults to assets/dialogue.json
+  diff <old file> <new file>            list lines and choices that were added, removed
+                                        or changed, e.g. against `git show HEAD:assets/dialogue.json`
+  recording-scripts <dir> [locale]      write a CSV and a Markdown recording script per speaker
+                                        into <dir>, defaults to the source locale
+  subtitles <scene> <file> [choice...] [--locale <locale>]
+                                        play through a scene and write its lines to a .srt or
+                                        .vtt file, picking the given choices in order, counted
+                                        from 0 among the ones shown, or `silent` to let a timed
+                                        choice run out";
+
+/// runs the tool named by the first argument, `None` when it isn't one so the game starts
+pub fn run(args: &[String]) -> Option<Result<(), String>> {
+    let (command, args) = args.split_first()?;
+    let result = match command.as_str() {
+        "export-po" => export(args, Format::Po),
+        "export-xliff" => export(args, Format::Xliff),
+        "import" => import(args),
+        "assign-ids" => assign_ids(args),
+        "diff" => diff(args),
+        "recording-scripts" => recording_scripts(args),
+        "subtitles" => subtitles(args),
+        "help" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// a translatable string with the context translators get to see next to it
+pub struct SourceString {
+    pub id: String,
+    pub scene: String,
+    pub line: usize,
+    pub speaker: String,
+    /// which part of the line the text comes from
+    pub kind: &'static str,
+    pub text: String,
+    /// the line's `voice` path, for the strings that get spoken
+    pub voice: Option<String>,
+    pub notes: Option<String>,
+    /// said out loud in the game, which a choice only is when it has no player text
+    pub spoken: bool,
+}
+
+/// every translatable string, in scene name and line order
+pub fn source_strings(data: &DialogueData) -> Vec<SourceString> {
+    let mut scene_names: Vec<&String> = data.scenes.keys().collect();
+    scene_names.sort();
+
+    let mut strings = Vec::new();
+    for scene_name in scene_names {
+        let scene = &data.scenes[scene_name];
+        if let Some(title) = &scene.title {
+            strings.push(SourceString {
+                id: DialogueScene::title_id(scene_name),
+                scene: scene_name.clone(),
+                line: 0,
+                speaker: String::new(),
+                kind: "title",
+                text: title.clone(),
+                voice: None,
+                notes: None,
+                spoken: false,
+            });
+        }
+
+        for (index, line) in scene.lines.iter().enumerate() {
+            let line_id = line.text_id(scene_name, index);
+            let mut push = |id: String,
+                            speaker: &str,
+                            kind,
+                            text: &str,
+                            notes: &Option<String>,
+                            spoken: bool| {
+                strings.push(SourceString {
+                    id,
+                    scene: scene_name.clone(),
+                    line: index,
+                    speaker: speaker.to_string(),
+                    kind,
+                    text: text.to_string(),
+                    // echoed player lines are spoken without a `voice` of their own
+                    voice: matches!(kind, "line" | "variant")
+                        .then(|| line.voice.clone())
+                        .flatten(),
+                    notes: notes.clone(),
+                    spoken,
+                });
+            };
+
+            if !line.text.is_empty() {
+                push(
+                    line_id.clone(),
+                    &line.speaker,
+                    "line",
+                    &line.text,
+                    &line.notes,
+                    true,
+                );
+            }
+
+            for (n, variant) in line.variants.iter().flatten().enumerate() {
+                push(
+                    format!("{line_id}.variant.{n}"),
+                    &line.speaker,
+                    "variant",
+                    variant,
+                    &line.notes,
+                    true,
+                );
+            }
+
+            for (n, choice) in line.choices.iter().flatten().enumerate() {
+                let choice_id = choice.text_id(&line_id, n);
+                if let Some(player_text) = &choice.player_text {
+                    push(
+                        format!("{choice_id}.player_text"),
+                        PLAYER_NAME,
+                        "player text",
+                        player_text,
+                        &choice.notes,
+                        true,
+                    );
+                }
+                // a choice without player text is echoed as the player's line
+                let echoed = choice.player_text.is_none();
+                push(
+                    choice_id,
+                    PLAYER_NAME,
+                    "choice",
+                    &choice.text,
+                    if echoed { &choice.notes } else { &None },
+                    echoed,
+                );
+            }
+
+            if let Some(player_text) = line
+                .silence
+                .as_ref()
+                .and_then(|silence| silence.player_text.as_ref())
+            {
+                push(
+                    format!("{line_id}.silence"),
+                    PLAYER_NAME,
+                    "silence",
+                    player_text,
+                    &None,
+                    true,
+                );
+            }
+        }
+    }
+
+    strings
+}
+
+pub fn load_data(path: &str) -> Result<DialogueData, String> {
+    let json = fs::read_to_string(path).map_err(|err| format!("failed to read {path}: {err}"))?;
+    DialogueData::from_json(&json).map_err(|err| format!("{path}: {err}"))
+}
+
+fn table_path(locale: &str) -> String {
+    format!("{LOCALES_DIR}/{locale}.json")
+}
+
+/// a table that doesn't exist yet is empty, one that can't be read is an error
+fn load_table(locale: &str) -> Result<HashMap<String, Translation>, String> {
+    let path = table_path(locale);
+    if !Path::new(&path).exists() {
+        return Ok(HashMap::new());
+    }
+    StringTables::load_table(&path).map_err(|err| format!("invalid string table {path}: {err}"))
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Po,
+    Xliff,
+}
+
+/// what a translator sees for one string, `previous` is set when its source changed
+struct ExportUnit<'a> {
+    source: &'a SourceString,
+    previous: Option<&'a Translation>,
+}
+
+impl ExportUnit<'_> {
+    /// the source the previous translation was made from, when the text has changed since
+    fn outdated_source(&self) -> Option<&str> {
+        self.previous
+            .and_then(Translation::source)
+            .filter(|translated_from| *translated_from != self.source.text)
+    }
+}
+
+fn export(args: &[String], format: Format) -> Result<(), String> {
+    let [locale, path, flags @ ..] = args else {
+        return Err(USAGE.to_string());
+    };
+    if locale == SOURCE_LOCALE {
+        return Err(format!("{SOURCE_LOCALE} is the source locale"));
+    }
+    let all = flags.iter().any(|flag| flag == "--all");
+
+    let data = load_data(DIALOGUE_PATH)?;
+    let table = load_table(locale)?;
+    let strings = source_strings(&data);
+
+    // hand written entries don't know their source, so they only come up again with --all
+    let units: Vec<ExportUnit> = strings
+        .iter()
+        .filter_map(|source| {
+            let translation = table.get(&source.id);
+            let outdated = translation.is_none_or(|translation| {
+                translation
+                    .source()
+                    .is_some_and(|translated_from| translated_from != source.text)
+            });
+            (all || outdated).then_some(ExportUnit {
+                source,
+                previous: translation,
+            })
+        })
+        .collect();
+
+    let contents = match format {
+        Format::Po => write_po(locale, &units),
+        Format::Xliff => write_xliff(locale, &units),
+    };
+    fs::write(path, contents).map_err(|err| format!("failed to write {path}: {err}"))?;
+
+    println!(
+        "exported {} of {} strings to {path}",
+        units.len(),
+        strings.len()
+    );
+    Ok(())
+}
+
+/// a translated string read back from a translator's file
+struct ImportUnit {
+    id: String,
+    source: String,
+    target: String,
+}
+
+fn import(args: &[String]) -> Result<(), String> {
+    let [locale, path] = args else {
+        return Err(USAGE.to_string());
+    };
+
+    let contents =
+        fs::read_to_string(path).map_err(|err| format!("failed to read {path}: {err}"))?;
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let units = match extension {
+        "po" => parse_po(&contents)?,
+        "xlf" | "xliff" => parse_xliff(&contents)?,
+        _ => {
+            return Err(format!(
+                "don't know how to import {path}, expected .po or .xliff"
+            ));
+        }
+    };
+
+    let data = load_data(DIALOGUE_PATH)?;
+    let known_ids: HashSet<String> = source_strings(&data)
+        .into_iter()
+        .map(|source| source.id)
+        .collect();
+
+    let mut table = load_table(locale)?;
+    let mut imported = 0;
+    for unit in units {
+        if !known_ids.contains(&unit.id) {
+            eprintln!(
+                "skipping \"{}\", no such string in {DIALOGUE_PATH}",
+                unit.id
+            );
+            continue;
+        }
+
+        table.insert(
+            unit.id,
+            Translation::Tracked {
+                text: unit.target,
+                source: unit.source,
+            },
+        );
+        imported += 1;
+    }
+
+    let table_path = table_path(locale);
+    StringTables::save_table(&table_path, &table)
+        .map_err(|err| format!("failed to write {table_path}: {err}"))?;
+
+    println!("imported {imported} strings into {table_path}");
+    Ok(())
+}
+
+fn assign_ids(args: &[String]) -> Result<(), String> {
+    let path = match args {
+        [] => DIALOGUE_PATH,
+        [path] => path.as_str(),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    // loading it first makes sure ids only get written into data the game accepts
+    let data = load_data(path)?;
+    let mut used_ids: HashSet<String> = data
+        .scenes
+        .values()
+        .flat_map(|scene| &scene.lines)
+        .flat_map(|line| {
+            let choice_ids = line.choices.iter().flatten().map(|choice| &choice.id);
+            std::iter::once(&line.id).chain(choice_ids)
+        })
+        .flatten()
+        .cloned()
+        .collect();
+
+    // edited as plain json so everything the dialogue types don't know about stays untouched
+    let json = fs::read_to_string(path).map_err(|err| format!("failed to read {path}: {err}"))?;
+    let mut root: Value = serde_json::from_str(&json).map_err(|err| err.to_string())?;
+    let scenes = root
+        .get_mut("scenes")
+        .and_then(Value::as_object_mut)
+        .ok_or("missing scenes")?;
+
+    let mut assigned = 0;
+    for (scene_name, scene) in scenes.iter_mut() {
+        let lines = match scene {
+            Value::Object(scene) => scene.get_mut("lines"),
+            lines => Some(lines),
+        };
+        let Some(lines) = lines.and_then(Value::as_array_mut) else {
+            continue;
+        };
+
+        for (index, line) in lines.iter_mut().enumerate() {
+            let Some(line) = line.as_object_mut() else {
+                continue;
+            };
+            let (line_id, added) = ensure_id(line, format!("{scene_name}.{index}"), &mut used_ids);
+            assigned += usize::from(added);
+
+            let choices = line.get_mut("choices").and_then(Value::as_array_mut);
+            for (n, choice) in choices.into_iter().flatten().enumerate() {
+                if let Some(choice) = choice.as_object_mut() {
+                    let (_, added) =
+                        ensure_id(choice, format!("{line_id}.choice.{n}"), &mut used_ids);
+                    assigned += usize::from(added);
+                }
+            }
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&root).map_err(|err| err.to_string())?;
+    fs::write(path, json + "\n").map_err(|err| format!("failed to write {path}: {err}"))?;
+
+    println!("assigned {assigned} ids in {path}");
+    Ok(())
+}
+
+/// gives `object` an id if it doesn't have one, returning the id and whether it's new.
+/// the implicit id is used when it's free, so existing string table entries keep matching
+fn ensure_id(
+    object: &mut Map<String, Value>,
+    implicit_id: String,
+    used_ids: &mut HashSet<String>,
+) -> (String, bool) {
+    if let Some(id) = object.get("id").and_then(Value::as_str) {
+        return (id.to_string(), false);
+    }
+
+    let mut id = implicit_id.clone();
+    let mut suffix = 2;
+    while used_ids.contains(&id) {
+        id = format!("{implicit_id}-{suffix}");
+        suffix += 1;
+    }
+    used_ids.insert(id.clone());
+
+    // put the id first where it's easy to spot
+    let rest = std::mem::take(object);
+    object.insert("id".to_string(), Value::String(id.clone()));
+    object.extend(rest);
+    (id, true)
+}
+
+/// a line or choice as far as a diff is concerned
+struct DiffEntry {
+    location: String,
+    summary: String,
+    hash: u64,
+}
+
+/// every line and choice by id, sorted so the output is stable
+fn diff_entries(data: &DialogueData) -> BTreeMap<String, DiffEntry> {
+    let mut entries = BTreeMap::new();
+    for (scene_name, scene) in &data.scenes {
+        for (index, line) in scene.lines.iter().enumerate() {
+            let line_id = line.text_id(scene_name, index);
+            let text = match &line.variants {
+                Some(variants) => variants.join(" / "),
+                None => line.text.clone(),
+            };
+            entries.insert(
+                line_id.clone(),
+                DiffEntry {
+                    location: format!("{scene_name}:{index}"),
+                    summary: format!("{}: {text}", line.speaker),
+                    hash: line.content_hash(),
+                },
+            );
+
+            for (n, choice) in line.choices.iter().flatten().enumerate() {
+                entries.insert(
+                    choice.text_id(&line_id, n),
+                    DiffEntry {
+                        location: format!("{scene_name}:{index} choice {n}"),
+                        summary: format!("> {}", choice.text),
+                        hash: choice.content_hash(),
+                    },
+                );
+            }
+        }
+    }
+    entries
+}
+
+fn diff(args: &[String]) -> Result<(), String> {
+    let [old_path, new_path] = args else {
+        return Err(USAGE.to_string());
+    };
+
+    let old = diff_entries(&load_data(old_path)?);
+    let new = diff_entries(&load_data(new_path)?);
+
+    let added: Vec<_> = new
+        .iter()
+        .filter(|(id, _)| !old.contains_key(*id))
+        .collect();
+    let removed: Vec<_> = old
+        .iter()
+        .filter(|(id, _)| !new.contains_key(*id))
+        .collect();
+    let changed: Vec<_> = new
+        .iter()
+        .filter_map(|(id, entry)| {
+            let before = old.get(id)?;
+            (before.hash != entry.hash).then_some((id, before, entry))
+        })
+        .collect();
+
+    for (id, entry) in &added {
+        println!("+ {id} ({}) {}", entry.location, entry.summary);
+    }
+    for (id, entry) in &removed {
+        println!("- {id} ({}) {}", entry.location, entry.summary);
+    }
+    for (id, before, after) in &changed {
+        println!("~ {id} ({}) {}", after.location, after.summary);
+        println!("    was: {}", before.summary);
+    }
+
+    println!(
+        "{} new, {} removed, {} changed",
+        added.len(),
+        removed.len(),
+        changed.len()
+    );
+    Ok(())
+}
+
+/// a line in an actor's recording script
+struct ScriptEntry {
+    id: String,
+    scene: String,
+    /// whatever was said right before, so the actor knows what they're answering
+    context: Option<String>,
+    text: String,
+    notes: Option<String>,
+    file: String,
+}
+
+fn recording_scripts(args: &[String]) -> Result<(), String> {
+    let (dir, locale) = match args {
+        [dir] => (dir, SOURCE_LOCALE),
+        [dir, locale] => (dir, locale.as_str()),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let data = load_data(DIALOGUE_PATH)?;
+    let tables = StringTables::load(LOCALES_DIR);
+    let strings = source_strings(&data);
+
+    let mut scripts: BTreeMap<String, Vec<ScriptEntry>> = BTreeMap::new();
+    // the last line of an earlier line in the scene, and the current line's own text, which is
+    // what the player's answers to it follow
+    let mut context: Option<String> = None;
+    let mut line_text: Option<String> = None;
+    let mut position: Option<(&str, usize)> = None;
+
+    // choice buttons are read, not spoken, unless they're echoed as the player's line
+    for source in strings.iter().filter(|source| source.spoken) {
+        if position != Some((&source.scene, source.line)) {
+            if position.is_some_and(|(scene, _)| scene == source.scene) {
+                context = line_text.take().or(context);
+            } else {
+                context = None;
+                line_text = None;
+            }
+            position = Some((&source.scene, source.line));
+        }
+
+        let text = tables
+            .translate(locale, &source.id, &source.text)
+            .into_owned();
+        let spoken_line = matches!(source.kind, "line" | "variant");
+        let entry_context = if spoken_line {
+            context.clone()
+        } else {
+            line_text.clone().or_else(|| context.clone())
+        };
+        if spoken_line && line_text.is_none() {
+            line_text = Some(format!("{}: {text}", source.speaker));
+        }
+
+        let speaker = match source.speaker.as_str() {
+            "" => "Narrator",
+            speaker => speaker,
+        };
+        scripts
+            .entry(speaker.to_string())
+            .or_default()
+            .push(ScriptEntry {
+                id: source.id.clone(),
+                scene: source.scene.clone(),
+                context: entry_context,
+                text,
+                notes: source.notes.clone(),
+                file: format!(
+                    "assets/{}",
+                    recording_path(source.voice.as_deref(), &source.id, locale)
+                ),
+            });
+    }
+
+    fs::create_dir_all(dir).map_err(|err| format!("failed to create {dir}: {err}"))?;
+    for (speaker, entries) in &scripts {
+        let name: String = speaker
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+
+        for (extension, contents) in [
+            ("csv", write_script_csv(entries)),
+            ("md", write_script_markdown(speaker, locale, entries)),
+        ] {
+            let path = Path::new(dir).join(format!("{name}.{extension}"));
+            fs::write(&path, contents)
+                .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+        }
+        println!("{speaker}: {} lines", entries.len());
+    }
+
+    println!("wrote {} recording scripts to {dir}", scripts.len());
+    Ok(())
+}
+
+fn write_script_csv(entries: &[ScriptEntry]) -> String {
+    let mut csv = String::from("id,scene,context,text,notes,file\n");
+    for entry in entries {
+        let fields = [
+            entry.id.as_str(),
+            &entry.scene,
+            entry.context.as_deref().unwrap_or_default(),
+            &entry.text,
+            entry.notes.as_deref().unwrap_or_default(),
+            &entry.file,
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_escape(field)).collect();
+        writeln!(csv, "{}", row.join(",")).unwrap();
+    }
+    csv
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_script_markdown(speaker: &str, locale: &str, entries: &[ScriptEntry]) -> String {
+    let mut markdown = format!("# {speaker}\n\nRecording script, locale `{locale}`.\n");
+    let mut scene = None;
+    for entry in entries {
+        if scene != Some(&entry.scene) {
+            writeln!(markdown, "\n## {}", entry.scene).unwrap();
+            scene = Some(&entry.scene);
+        }
+
+        writeln!(markdown, "\n### `{}`\n", entry.id).unwrap();
+        if let Some(context) = &entry.context {
+            writeln!(markdown, "> {context}\n").unwrap();
+        }
+        writeln!(markdown, "**{}**\n", entry.text).unwrap();
+        if let Some(notes) = &entry.notes {
+            writeln!(markdown, "_{notes}_\n").unwrap();
+        }
+        writeln!(markdown, "File: `{}`", entry.file).unwrap();
+    }
+    markdown
+}
+
+/// characters a viewer reads per second, for lines without an `auto_time`
+const READING_SPEED: f32 = 15.0;
+const MIN_CUE_SECONDS: f32 = 1.5;
+
+struct Cue {
+    start: f32,
+    end: f32,
+    speaker: String,
+    text: String,
+}
+
+#[derive(Clone, Copy)]
+enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+/// plays a scene the way the game's systems would, only without anyone to react to the
+/// triggers besides the variables they set
+fn subtitles(args: &[String]) -> Result<(), String> {
+    let [scene, path, rest @ ..] = args else {
+        return Err(USAGE.to_string());
+    };
+    let format = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("srt") => SubtitleFormat::Srt,
+        Some("vtt") => SubtitleFormat::Vtt,
+        _ => return Err(format!("{path} should end in .srt or .vtt")),
+    };
+
+    let mut locale = SOURCE_LOCALE.to_string();
+    let mut picks = Vec::new();
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--locale" => locale = rest.next().ok_or(USAGE)?.clone(),
+            "silent" => picks.push(None),
+            pick => picks.push(Some(
+                pick.parse::<usize>()
+                    .map_err(|_| format!("\"{pick}\" is not a choice"))?,
+            )),
+        }
+    }
+
+    let data = load_data(DIALOGUE_PATH)?;
+    if !data.scenes.contains_key(scene) {
+        return Err(format!("there is no scene \"{scene}\""));
+    }
+    let tables = StringTables::load(LOCALES_DIR);
+    // DIALOGUE_SEED picks the same random variants the game would with it
+    let mut rng = DialogueRng::from_env();
+
+    let mut manager = DialogueManager::new(data);
+    manager.enter_scene(scene.clone(), 0);
+
+    let mut cues = Vec::new();
+    let mut time = 0.0;
+    let mut add_cue = |speaker: &str, text: String, auto_time: Option<f32>| {
+        let duration = auto_time
+            .unwrap_or_else(|| (text.chars().count() as f32 / READING_SPEED).max(MIN_CUE_SECONDS));
+        cues.push(Cue {
+            start: time,
+            end: time + duration,
+            speaker: speaker.to_string(),
+            text,
+        });
+        time += duration;
+    };
+
+    let mut picks = picks.into_iter();
+    loop {
+        if let Some(line) = manager.ephemeral_line.take() {
+            let shown = ShownText {
+                id: line.id.clone().unwrap_or_default(),
+                source: line.text.clone(),
+            };
+            add_cue(
+                &line.speaker,
+                manager.render(&shown, &tables, &locale),
+                line.auto_time,
+            );
+            continue;
+        }
+
+        let next_line = manager.next_shown_line();
+        let Some(line) = next_line else {
+            break;
+        };
+
+        manager.visit_current_line();
+        let shown = manager.line_text(&line, &mut rng);
+        add_cue(
+            &line.speaker,
+            manager.render(&shown, &tables, &locale),
+            line.auto_time,
+        );
+        manager.fire_triggers(&line.triggers);
+
+        let presented = line
+            .choices
+            .as_ref()
+            .map(|choices| manager.presentable_choices(choices))
+            .unwrap_or_default();
+        if presented.is_empty() {
+            manager.finish_line(&line);
+            continue;
+        }
+
+        let location = format!("{}:{}", manager.current_scene, manager.current_line);
+        let Some(pick) = picks.next() else {
+            println!("stopped at the choice in {location}, pass more choices to go on");
+            break;
+        };
+
+        // a timed out choice falls back to its default, like handle_choice_timeout
+        let pick = pick.or_else(|| {
+            line.default_choice
+                .and_then(|default| presented.iter().position(|&i| i == default))
+        });
+        let (choice, choice_index) = match pick {
+            Some(pick) => {
+                let &choice_index = presented.get(pick).ok_or_else(|| {
+                    format!(
+                        "{location} shows {} choices, there is no choice {pick}",
+                        presented.len()
+                    )
+                })?;
+                let choice_key = manager.choice_key(choice_index);
+                manager.taken_choices.insert(choice_key);
+                let choices = line.choices.as_ref().unwrap();
+                (choices[choice_index].clone(), Some(choice_index))
+            }
+            None => {
+                let silence = line.silence.clone().unwrap_or_default();
+                (DialogueChoice::from(silence), None)
+            }
+        };
+
+        manager.current_choice_indices = presented;
+        manager.choose(&choice, choice_index);
+    }
+
+    let unused = picks.count();
+    if unused > 0 {
+        println!("the dialogue ended with {unused} choices left over");
+    }
+
+    fs::write(path, write_subtitles(&cues, format))
+        .map_err(|err| format!("failed to write {path}: {err}"))?;
+    println!("wrote {} subtitles to {path}", cues.len());
+    Ok(())
+}
+
+fn write_subtitles(cues: &[Cue], format: SubtitleFormat) -> String {
+    let mut subtitles = String::new();
+    if let SubtitleFormat::Vtt = format {
+        subtitles.push_str("WEBVTT\n");
+    }
+
+    for (index, cue) in cues.iter().enumerate() {
+        match format {
+            SubtitleFormat::Srt if index > 0 => subtitles.push('\n'),
+            SubtitleFormat::Srt => {}
+            SubtitleFormat::Vtt => subtitles.push('\n'),
+        }
+        if let SubtitleFormat::Srt = format {
+            writeln!(subtitles, "{}", index + 1).unwrap();
+        }
+
+        let separator = match format {
+            SubtitleFormat::Srt => ',',
+            SubtitleFormat::Vtt => '.',
+        };
+        writeln!(
+            subtitles,
+            "{} --> {}",
+            cue_time(cue.start, separator),
+            cue_time(cue.end, separator)
+        )
+        .unwrap();
+
+        if cue.speaker.is_empty() {
+            writeln!(subtitles, "{}", cue.text).unwrap();
+        } else {
+            writeln!(subtitles, "{}: {}", cue.speaker, cue.text).unwrap();
+        }
+    }
+    subtitles
+}
+
+/// `hh:mm:ss,mmm`, with a `.` before the milliseconds for WebVTT
+fn cue_time(seconds: f32, separator: char) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn write_po(locale: &str, units: &[ExportUnit]) -> String {
+    let mut po = String::new();
+    writeln!(po, "msgid \"\"").unwrap();
+    writeln!(po, "msgstr \"\"").unwrap();
+    writeln!(po, "\"Language: {locale}\\n\"").unwrap();
+    writeln!(po, "\"MIME-Version: 1.0\\n\"").unwrap();
+    writeln!(po, "\"Content-Type: text/plain; charset=UTF-8\\n\"").unwrap();
+    writeln!(po, "\"Content-Transfer-Encoding: 8bit\\n\"").unwrap();
+
+    for unit in units {
+        let source = unit.source;
+        writeln!(po).unwrap();
+        writeln!(po, "#. {}, {}", source.speaker, source.kind).unwrap();
+        writeln!(po, "#: {}:{}", source.scene, source.line).unwrap();
+
+        // a changed source keeps the old translation as a starting point, flagged for review
+        if let Some(translated_from) = unit.outdated_source() {
+            writeln!(po, "#, fuzzy").unwrap();
+            writeln!(po, "#| msgid \"{}\"", po_escape(translated_from)).unwrap();
+        }
+
+        writeln!(po, "msgctxt \"{}\"", po_escape(&source.id)).unwrap();
+        writeln!(po, "msgid \"{}\"", po_escape(&source.text)).unwrap();
+        let target = unit.previous.map(Translation::text).unwrap_or_default();
+        writeln!(po, "msgstr \"{}\"", po_escape(target)).unwrap();
+    }
+
+    po
+}
+
+fn po_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+fn po_unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+#[derive(Default)]
+struct PoEntry {
+    context: Option<String>,
+    id: String,
+    target: String,
+    fuzzy: bool,
+    /// the keyword the last quoted string belonged to, for continuation lines
+    field: Option<&'static str>,
+}
+
+/// reads the entries this tool writes, skipping untranslated and fuzzy ones
+fn parse_po(contents: &str) -> Result<Vec<ImportUnit>, String> {
+    let mut units = Vec::new();
+    let mut entry = PoEntry::default();
+
+    let mut flush = |entry: &mut PoEntry| {
+        let entry = std::mem::take(entry);
+        if let Some(id) = entry.context
+            && !entry.target.is_empty()
+            && !entry.fuzzy
+        {
+            units.push(ImportUnit {
+                id,
+                source: entry.id,
+                target: entry.target,
+            });
+        }
+    };
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let quoted = |text: &str| -> Result<String, String> {
+            let text = text.trim();
+            text.strip_prefix('"')
+                .and_then(|text| text.strip_suffix('"'))
+                .map(po_unescape)
+                .ok_or_else(|| format!("line {}: expected a quoted string", number + 1))
+        };
+
+        if line.is_empty() {
+            flush(&mut entry);
+        } else if let Some(comment) = line.strip_prefix('#') {
+            if entry.field.is_some() {
+                flush(&mut entry);
+            }
+            if let Some(flags) = comment.strip_prefix(',') {
+                entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+            }
+        } else if let Some(text) = line.strip_prefix("msgctxt ") {
+            if entry.field.is_some() {
+                flush(&mut entry);
+            }
+            entry.context = Some(quoted(text)?);
+            entry.field = Some("msgctxt");
+        } else if let Some(text) = line.strip_prefix("msgid ") {
+            if entry.field == Some("msgstr") {
+                flush(&mut entry);
+            }
+            entry.id = quoted(text)?;
+            entry.field = Some("msgid");
+        } else if let Some(text) = line.strip_prefix("msgstr ") {
+            entry.target = quoted(text)?;
+            entry.field = Some("msgstr");
+        } else if line.starts_with('"') {
+            let text = quoted(line)?;
+            match entry.field {
+                Some("msgctxt") => entry.context.get_or_insert_default().push_str(&text),
+                Some("msgid") => entry.id.push_str(&text),
+                Some("msgstr") => entry.target.push_str(&text),
+                _ => return Err(format!("line {}: unexpected string", number + 1)),
+            }
+        } else {
+            return Err(format!("line {}: unexpected \"{line}\"", number + 1));
+        }
+    }
+    flush(&mut entry);
+
+    Ok(units)
+}
+
+fn write_xliff(locale: &str, units: &[ExportUnit]) -> String {
+    let mut xliff = String::new();
+    writeln!(xliff, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        xliff,
+        r#"<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">"#
+    )
+    .unwrap();
+    writeln!(
+        xliff,
+        r#"  <file original="{DIALOGUE_PATH}" source-language="{SOURCE_LOCALE}" target-language="{}" datatype="plaintext">"#,
+        xml_escape(locale)
+    )
+    .unwrap();
+    writeln!(xliff, "    <body>").unwrap();
+
+    for unit in units {
+        let source = unit.source;
+        writeln!(
+            xliff,
+            r#"      <trans-unit id="{}" resname="{}:{}">"#,
+            xml_escape(&source.id),
+            xml_escape(&source.scene),
+            source.line
+        )
+        .unwrap();
+        writeln!(
+            xliff,
+            "        <source>{}</source>",
+            xml_escape(&source.text)
+        )
+        .unwrap();
+        if let Some(previous) = unit.previous {
+            let state = if unit.outdated_source().is_some() {
+                "needs-review-translation"
+            } else {
+                "translated"
+            };
+            writeln!(
+                xliff,
+                r#"        <target state="{state}">{}</target>"#,
+                xml_escape(previous.text())
+            )
+            .unwrap();
+        }
+        writeln!(
+            xliff,
+            r#"        <note from="speaker">{}</note>"#,
+            xml_escape(&source.speaker)
+        )
+        .unwrap();
+        writeln!(xliff, r#"        <note>{}</note>"#, source.kind).unwrap();
+        writeln!(xliff, "      </trans-unit>").unwrap();
+    }
+
+    writeln!(xliff, "    </body>").unwrap();
+    writeln!(xliff, "  </file>").unwrap();
+    writeln!(xliff, "</xliff>").unwrap();
+    xliff
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// decodes the predefined entities and numeric references like `&#10;` and `&#x2026;`,
+/// anything else is kept as it is
+fn xml_unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "amp" => '&',
+                reference => {
+                    let number = reference.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// the value of `name="..."` inside a start tag
+fn xml_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+/// the start tag and text of the first `<name>` element in `xml`, and where it ends
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str, usize)> {
+    let start = xml.find(&format!("<{name}"))?;
+    let tag_end = start + xml[start..].find('>')?;
+    let tag = &xml[start..=tag_end];
+    if tag.ends_with("/>") {
+        return Some((tag, "", tag_end + 1));
+    }
+    let closing = format!("</{name}>");
+    let content_end = tag_end + xml[tag_end..].find(&closing)?;
+    Some((
+        tag,
+        &xml[tag_end + 1..content_end],
+        content_end + closing.len(),
+    ))
+}
+
+/// reads the trans-units this tool writes, skipping ones without a finished target. this is
+/// not a full xml parser, inline markup inside segments is kept as text
+fn parse_xliff(contents: &str) -> Result<Vec<ImportUnit>, String> {
+    let mut units = Vec::new();
+    let mut rest = contents;
+
+    while let Some((tag, body, end)) = xml_element(rest, "trans-unit") {
+        rest = &rest[end..];
+        let id = xml_attribute(tag, "id").ok_or("trans-unit without an id")?;
+
+        let Some((_, source, _)) = xml_element(body, "source") else {
+            return Err(format!("trans-unit \"{id}\" has no source"));
+        };
+        let Some((target_tag, target, _)) = xml_element(body, "target") else {
+            continue;
+        };
+        let state = xml_attribute(target_tag, "state");
+        if target.is_empty() || matches!(state, Some("new" | "needs-review-translation")) {
+            continue;
+        }
+
+        units.push(ImportUnit {
+            id: xml_unescape(id),
+            source: xml_unescape(source),
+            target: xml_unescape(target),
+        });
+    }
+
+    Ok(units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(id: &str, text: &str) -> SourceString {
+        SourceString {
+            id: id.to_string(),
+            scene: "bar".to_string(),
+            line: 0,
+            speaker: "Joe".to_string(),
+            kind: "line",
+            text: text.to_string(),
+            voice: None,
+            notes: None,
+            spoken: true,
+        }
+    }
+
+    fn tracked(text: &str, source: &str) -> Translation {
+        Translation::Tracked {
+            text: text.to_string(),
+            source: source.to_string(),
+        }
+    }
+
+    const TRICKY: &str = "say \"hi\" to C:\\bar\nthen\tleave";
+
+    /// a translated string with quotes, backslashes and escapes, one whose source changed
+    /// since it was translated and one that was never translated
+    fn round_trip(write: fn(&str, &[ExportUnit]) -> String) -> Vec<ImportUnit> {
+        let strings = [
+            source("bar.greeting", TRICKY),
+            source("bar.outdated", "new text"),
+            source("bar.new", "untranslated"),
+        ];
+        let translations = [
+            tracked("sag \"hallo\" zu C:\\bar\ndann\tgehen", TRICKY),
+            tracked("alter text", "old text"),
+        ];
+        let units = [
+            ExportUnit {
+                source: &strings[0],
+                previous: Some(&translations[0]),
+            },
+            ExportUnit {
+                source: &strings[1],
+                previous: Some(&translations[1]),
+            },
+            ExportUnit {
+                source: &strings[2],
+                previous: None,
+            },
+        ];
+
+        match write("de", &units) {
+            po if po.starts_with("msgid") => parse_po(&po).unwrap(),
+            xliff => parse_xliff(&xliff).unwrap(),
+        }
+    }
+
+    #[test]
+    fn po_round_trip_keeps_escapes_and_skips_fuzzy_and_untranslated_entries() {
+        let units = round_trip(write_po);
+
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].id, "bar.greeting");
+        assert_eq!(units[0].source, TRICKY);
+        assert_eq!(units[0].target, "sag \"hallo\" zu C:\\bar\ndann\tgehen");
+    }
+
+    #[test]
+    fn po_continuation_lines_are_joined() {
+        let po = r#"msgid ""
+msgstr ""
+"Language: de\n"
+
+#, fuzzy
+msgctxt "bar.fuzzy"
+msgid "a"
+msgstr "b"
+
+msgctxt "bar."
+"long"
+msgid ""
+"first line\n"
+"second line"
+msgstr ""
+"erste Zeile\n"
+"zweite Zeile"
+"#;
+        let units = parse_po(po).unwrap();
+
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].id, "bar.long");
+        assert_eq!(units[0].source, "first line\nsecond line");
+        assert_eq!(units[0].target, "erste Zeile\nzweite Zeile");
+    }
+
+    #[test]
+    fn po_rejects_unquoted_strings() {
+        assert!(parse_po("msgctxt bar.0\n").is_err());
+    }
+
+    #[test]
+    fn xliff_round_trip_keeps_escapes_and_skips_entries_needing_review() {
+        let units = round_trip(write_xliff);
+
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].id, "bar.greeting");
+        assert_eq!(units[0].source, TRICKY);
+        assert_eq!(units[0].target, "sag \"hallo\" zu C:\\bar\ndann\tgehen");
+    }
+
+    #[test]
+    fn xliff_skips_unfinished_targets_and_decodes_references() {
+        let xliff = r#"<xliff version="1.2">
+  <file original="assets/dialogue.json" source-language="en" target-language="de">
+    <body>
+      <trans-unit id="bar.new">
+        <source>a</source>
+        <target state="new">b</target>
+      </trans-unit>
+      <trans-unit id="bar.review">
+        <source>a</source>
+        <target state="needs-review-translation">b</target>
+      </trans-unit>
+      <trans-unit id="bar.empty">
+        <source>a</source>
+        <target/>
+      </trans-unit>
+      <trans-unit id="bar.&quot;0&quot;">
+        <source>fish &amp; chips&#10;&lt;now&gt;</source>
+        <target state="translated">Fisch &amp;amp; Pommes&#xA;&#x2026; &apos;jetzt&apos; &bogus; &#xZZ;</target>
+      </trans-unit>
+    </body>
+  </file>
+</xliff>"#;
+        let units = parse_xliff(xliff).unwrap();
+
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].id, "bar.\"0\"");
+        assert_eq!(units[0].source, "fish & chips\n<now>");
+        assert_eq!(
+            units[0].target,
+            "Fisch &amp; Pommes\n\u{2026} 'jetzt' &bogus; &#xZZ;"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::asset::io::file::FileAssetReader;
+
+use bevy::{
+    audio::{Decodable, Source},
+    ecs::system::SystemParam,
+    prelude::*,
+};
+
+use crate::dialogue::{components::VoiceClip, resources::SOURCE_LOCALE};
+
+/// recordings live in `assets/voice/<locale>/`
+pub const VOICE_DIR: &str = "voice";
+
+/// plays and stops the recorded voice-over of dialogue lines
+#[derive(SystemParam)]
+pub struct DialogueVoice<'w, 's> {
+    asset_server: Res<'w, AssetServer>,
+    clips: Query<'w, 's, Entity, With<VoiceClip>>,
+}
+
+impl DialogueVoice<'_, '_> {
+    pub fn play(&self, commands: &mut Commands, voice: Option<&str>, id: &str, locale: &str) {
+        let Some(path) = voice_path(voice, id, locale) else {
+            if let Some(voice) = voice {
+                warn!("voice clip \"{voice}\" for \"{id}\" does not exist");
+            }
+            return;
+        };
+
+        commands.spawn((
+            AudioPlayer::new(self.asset_server.load(path)),
+            PlaybackSettings::DESPAWN,
+            VoiceClip,
+        ));
+    }
+
+    pub fn stop(&self, commands: &mut Commands) {
+        for entity in &self.clips {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.clips.is_empty()
+    }
+}
+
+/// the line's `voice` path with `{locale}` filled in, or `voice/<locale>/<id>.ogg` when it
+/// doesn't have one. lines that haven't been recorded in the locale yet fall back to the
+/// source locale's recording, and `None` means there's nothing to play
+pub fn voice_path(voice: Option<&str>, id: &str, locale: &str) -> Option<String> {
+    [locale, SOURCE_LOCALE]
+        .map(|locale| recording_path(voice, id, locale))
+        .into_iter()
+        .find(|path| recording_exists(path))
+}
+
+/// looks in the `assets` directory bevy loads from, which is under `BEVY_ASSET_ROOT`,
+/// `CARGO_MANIFEST_DIR` or next to the executable rather than in the working directory
+#[cfg(not(target_arch = "wasm32"))]
+fn recording_exists(path: &str) -> bool {
+    FileAssetReader::get_base_path()
+        .join("assets")
+        .join(path)
+        .exists()
+}
+
+/// the web can't look before loading, a missing clip fails to load instead and is dropped
+/// by `cleanup_failed_voice`
+#[cfg(target_arch = "wasm32")]
+fn recording_exists(_path: &str) -> bool {
+    true
+}
+
+/// where the recording of a line in `locale` goes, relative to `assets`
+pub fn recording_path(voice: Option<&str>, id: &str, locale: &str) -> String {
+    match voice {
+        Some(voice) => voice.replace("{locale}", locale),
+        None => format!("{VOICE_DIR}/{locale}/{id}.ogg"),
+    }
+}
+
+const BLIP_SAMPLE_RATE: u32 = 44_100;
+const BLIP_DURATION: f32 = 0.05;
+const BLIP_VOLUME: f32 = 0.2;
+
+/// the shape of a blip's tone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
+}
+
+impl Waveform {
+    /// the wave's value at `phase`, a fraction of one period
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+/// a short synthesized tone played per revealed character for speakers without voice-over
+#[derive(Asset, TypePath, Debug, Clone, Copy)]
+pub struct Blip {
+    pub frequency: f32,
+    pub waveform: Waveform,
+}
+
+impl Decodable for Blip {
+    type DecoderItem = f32;
+    type Decoder = BlipDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        BlipDecoder {
+            blip: *self,
+            sample: 0,
+            length: (BLIP_DURATION * BLIP_SAMPLE_RATE as f32) as usize,
+        }
+    }
+}
+
+pub struct BlipDecoder {
+    blip: Blip,
+    sample: usize,
+    length: usize,
+}
+
+impl Iterator for BlipDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample >= self.length {
+            return None;
+        }
+
+        let time = self.sample as f32 / BLIP_SAMPLE_RATE as f32;
+        let phase = (time * self.blip.frequency).fract();
+        // fades out linearly so the tone doesn't end in a click
+        let envelope = 1.0 - self.sample as f32 / self.length as f32;
+        self.sample += 1;
+
+        Some(self.blip.waveform.sample(phase) * envelope * BLIP_VOLUME)
+    }
+}
+
+impl Source for BlipDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.length - self.sample)
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        BLIP_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(BLIP_DURATION))
+    }
+}
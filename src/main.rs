@@ -8,7 +8,7 @@ use bevy::{
     prelude::*,
 };
 
-use crate::dialogue::{DialoguePlugin, components::*};
+use crate::dialogue::{DialoguePlugin, components::*, events::*, voice::Waveform};
 
 pub const ORIGINAL_JOE_POSITION: Vec3 = Vec3::new(3.0, 0.0, 0.0);
 const PLAYER_NAME: &str = "Joe Swanson";
@@ -32,14 +32,74 @@ impl Material for StripedMaterial {
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = dialogue::tools::run(&args) {
+        if let Err(err) = result {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(DialoguePlugin)
         .add_plugins(MaterialPlugin::<StripedMaterial>::default())
         .add_systems(Startup, setup_scene)
+        .add_systems(Update, log_dialogue_events)
+        .add_observer(on_dialogue_ended)
         .run();
 }
 
+/// stands in for quest, audio and achievement systems reacting to the dialogue
+fn log_dialogue_events(
+    mut started_events: EventReader<DialogueStartedEvent>,
+    mut line_events: EventReader<LineShownEvent>,
+    mut choices_events: EventReader<ChoicesPresentedEvent>,
+    mut choice_events: EventReader<ChoiceMadeEvent>,
+    mut scene_events: EventReader<SceneChangedEvent>,
+) {
+    for event in started_events.read() {
+        info!("dialogue started in {}", event.scene);
+    }
+
+    for event in line_events.read() {
+        match event.line {
+            Some(line) => info!("{}:{line} {}: {}", event.scene, event.speaker, event.text),
+            None => info!("{}: {}: {}", event.scene, event.speaker, event.text),
+        }
+    }
+
+    for event in choices_events.read() {
+        info!(
+            "{}:{} offers {} choices",
+            event.scene,
+            event.line,
+            event.choices.len()
+        );
+    }
+
+    for event in choice_events.read() {
+        match event.choice {
+            Some(choice) => info!("{}:{} picked choice {choice}", event.scene, event.line),
+            None => info!("{}:{} stayed silent", event.scene, event.line),
+        }
+    }
+
+    for event in scene_events.read() {
+        info!("now in scene {}", event.scene);
+    }
+}
+
+fn on_dialogue_ended(trigger: Trigger<DialogueEndedEvent>) {
+    let event = trigger.event();
+    if event.cancelled {
+        info!("dialogue cancelled in {}", event.scene);
+    } else {
+        info!("dialogue ended in {}", event.scene);
+    }
+}
+
 fn setup_scene(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -84,6 +144,11 @@ fn setup_scene(
         Speaker {
             name: "Peter Griffin".to_string(),
         },
+        BlipProfile {
+            pitch: 140.0,
+            waveform: Waveform::Square,
+            variance: 0.08,
+        },
     ));
 
     commands.spawn((
@@ -98,6 +163,11 @@ fn setup_scene(
         Speaker {
             name: "Glenn Quagmire".to_string(),
         },
+        BlipProfile {
+            pitch: 220.0,
+            waveform: Waveform::Sawtooth,
+            variance: 0.15,
+        },
     ));
 
     commands.spawn((
@@ -112,6 +182,11 @@ fn setup_scene(
         Speaker {
             name: "Cleveland Brown".to_string(),
         },
+        BlipProfile {
+            pitch: 110.0,
+            waveform: Waveform::Sine,
+            variance: 0.05,
+        },
     ));
 
     commands.spawn((
@@ -126,6 +201,11 @@ fn setup_scene(
         Speaker {
             name: PLAYER_NAME.to_string(),
         },
+        BlipProfile {
+            pitch: 170.0,
+            waveform: Waveform::Triangle,
+            variance: 0.1,
+        },
         Actor {
             name: PLAYER_NAME.to_string(),
         },